@@ -1,10 +1,29 @@
 use anchor_lang::prelude::*;
 use anchor_lang::solana_program::bpf_loader_upgradeable::{self, UpgradeableLoaderState};
-use anchor_spl::token::{self, Mint, Token, TokenAccount, Transfer};
+use anchor_lang::system_program;
+use anchor_spl::associated_token::AssociatedToken;
+use anchor_spl::token::{self, CloseAccount, Mint, Token, TokenAccount, Transfer};
 use bincode::deserialize;
+use solana_instructions_sysvar::get_instruction_relative;
+use solana_sdk_ids::{ed25519_program, sysvar::instructions as instructions_sysvar_id};
 
 const MAX_FEE_BPS: u16 = 100;
 const UNCLAIMED_AUTHORITY_SEED: &[u8] = b"unclaimed_authority";
+const WITHDRAW_WINDOW_SECONDS: i64 = 24 * 60 * 60;
+const WITHDRAW_DELAY_SECONDS: i64 = 24 * 60 * 60;
+const WITHDRAW_INTENT_DOMAIN: &[u8] = b"tipcoin:withdraw_intent:v1";
+const TIP_INTENT_DOMAIN: &[u8] = b"tipcoin:tip_intent:v1";
+const TIP_MANY_INTENT_DOMAIN: &[u8] = b"tipcoin:tip_many_intent:v1";
+const WALLET_TIP_INTENT_DOMAIN: &[u8] = b"tipcoin:wallet_tip_intent:v1";
+const POOL_CONTRIBUTION_INTENT_DOMAIN: &[u8] = b"tipcoin:pool_contribution_intent:v1";
+const INVOICE_PAYMENT_INTENT_DOMAIN: &[u8] = b"tipcoin:invoice_payment_intent:v1";
+const MAX_SESSION_SECONDS: i64 = 7 * 24 * 60 * 60;
+const TIP_RECEIPT_RETENTION_SECONDS: i64 = 7 * 24 * 60 * 60;
+const INVOICE_RETENTION_SECONDS: i64 = 7 * 24 * 60 * 60;
+const MAX_MEMO_LEN: usize = 200;
+const MAX_TIP_MANY_RECIPIENTS: usize = 20;
+const MAX_TIP_DISPUTE_WINDOW_SECONDS: i64 = 7 * 24 * 60 * 60;
+const MIN_SUBSCRIPTION_INTERVAL_SECONDS: i64 = 60 * 60;
 
 declare_id!("BRTHhxE55gYbzhHtCaaRd6ngGL6j3pJHD4Pn4nBwJweh");
 
@@ -58,6 +77,7 @@ pub mod tipcoin {
 
         let authority_key = ctx.accounts.authority.key();
         let config_mint = ctx.accounts.config.token_mint;
+        let config_relayer = ctx.accounts.config.relayer;
         let unclaimed_authority = unclaimed_authority_key(ctx.program_id);
 
         let vault = &mut ctx.accounts.vault;
@@ -102,8 +122,13 @@ pub mod tipcoin {
             allowance.hashed_user_id = hashed_user_id;
         }
         allowance.authority = authority_key;
+        allowance.spender = config_relayer;
         allowance.cap = 0;
         allowance.remaining = 0;
+        allowance.period_seconds = 0;
+        allowance.period_start = 0;
+        allowance.expires_at = 0;
+        allowance.max_per_tip = 0;
 
         emit!(VaultClaimed {
             authority: authority_key,
@@ -141,30 +166,89 @@ pub mod tipcoin {
             vault_bump,
             hashed_user_id: ctx.accounts.vault.hashed_user_id,
             amount,
+            funder: ctx.accounts.authority.key(),
         });
 
         Ok(())
     }
 
-    pub fn approve_allowance(ctx: Context<ApproveAllowance>, amount: u64) -> Result<()> {
+    pub fn deposit_to(
+        ctx: Context<DepositTo>,
+        hashed_user_id: [u8; 32],
+        amount: u64,
+    ) -> Result<()> {
+        require!(amount > 0, TipError::InvalidDepositAmount);
+        require!(
+            hashed_user_id.iter().any(|byte| *byte != 0),
+            TipError::InvalidHashedUserId
+        );
+
+        let config_token_mint = ctx.accounts.config.token_mint;
+        let unclaimed_authority = unclaimed_authority_key(ctx.program_id);
+        let vault = &mut ctx.accounts.vault;
+
+        init_or_verify_recipient_vault(
+            vault,
+            hashed_user_id,
+            config_token_mint,
+            unclaimed_authority,
+        )?;
+
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.funder_token_account.to_account_info(),
+            to: ctx.accounts.vault_token_account.to_account_info(),
+            authority: ctx.accounts.funder.to_account_info(),
+        };
+
+        let cpi_ctx = CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts);
+        token::transfer(cpi_ctx, amount)?;
+
+        emit!(DepositEvent {
+            authority: vault.authority,
+            vault: vault.key(),
+            vault_bump: ctx.bumps.vault,
+            hashed_user_id,
+            amount,
+            funder: ctx.accounts.funder.key(),
+        });
+
+        Ok(())
+    }
+
+    pub fn approve_allowance(
+        ctx: Context<ApproveAllowance>,
+        args: ApproveAllowanceArgs,
+    ) -> Result<()> {
+        require!(args.period_seconds >= 0, TipError::InvalidAllowancePeriod);
+        require!(args.spender != Pubkey::default(), TipError::InvalidSpender);
+
+        let now = Clock::get()?.unix_timestamp;
+        require!(
+            args.valid_until == 0 || now <= args.valid_until,
+            TipError::ApprovalExpired
+        );
+
+        let vault = &ctx.accounts.vault;
         let allowance = &mut ctx.accounts.allowance;
 
-        allowance.cap = amount;
-        allowance.remaining = amount;
+        if allowance.hashed_user_id == [0u8; 32] {
+            allowance.hashed_user_id = vault.hashed_user_id;
+            allowance.spender = args.spender;
+        }
+        allowance.authority = vault.authority;
+        allowance.cap = args.amount;
+        allowance.remaining = args.amount;
+        allowance.period_seconds = args.period_seconds;
+        allowance.period_start = now;
+        allowance.expires_at = args.expires_at;
+        allowance.max_per_tip = args.max_per_tip;
 
         let (vault, vault_bump) = Pubkey::find_program_address(
             &[b"vault", allowance.hashed_user_id.as_ref()],
             ctx.program_id,
         );
 
-        emit!(AllowanceUpdated {
-            authority: allowance.authority,
-            vault,
-            vault_bump,
-            hashed_user_id: allowance.hashed_user_id,
-            cap: allowance.cap,
-            remaining: allowance.remaining,
-        });
+        emit_allowance_updated(allowance, vault, vault_bump);
 
         Ok(())
     }
@@ -173,19 +257,209 @@ pub mod tipcoin {
         let allowance = &mut ctx.accounts.allowance;
         allowance.cap = 0;
         allowance.remaining = 0;
+        allowance.period_seconds = 0;
+
+        let (vault, vault_bump) = Pubkey::find_program_address(
+            &[b"vault", allowance.hashed_user_id.as_ref()],
+            ctx.program_id,
+        );
+
+        emit_allowance_updated(allowance, vault, vault_bump);
+
+        Ok(())
+    }
+
+    pub fn adjust_allowance(
+        ctx: Context<AdjustAllowance>,
+        delta: AllowanceDelta,
+        expected_remaining: Option<u64>,
+    ) -> Result<()> {
+        let allowance = &mut ctx.accounts.allowance;
+        let now = Clock::get()?.unix_timestamp;
+        refresh_allowance_window(allowance, now);
+
+        if let Some(expected_remaining) = expected_remaining {
+            require!(
+                allowance.remaining == expected_remaining,
+                TipError::AllowanceRemainingMismatch
+            );
+        }
+
+        match delta {
+            AllowanceDelta::Increase(amount) => {
+                require!(amount > 0, TipError::InvalidAllowanceDelta);
+                allowance.cap = allowance
+                    .cap
+                    .checked_add(amount)
+                    .ok_or(TipError::InvalidAllowanceDelta)?;
+                allowance.remaining = allowance
+                    .remaining
+                    .checked_add(amount)
+                    .ok_or(TipError::InvalidAllowanceDelta)?;
+            }
+            AllowanceDelta::Decrease(amount) => {
+                require!(amount > 0, TipError::InvalidAllowanceDelta);
+                allowance.cap = allowance.cap.saturating_sub(amount);
+                allowance.remaining = allowance.remaining.saturating_sub(amount);
+            }
+        }
+
+        let (vault, vault_bump) = Pubkey::find_program_address(
+            &[b"vault", allowance.hashed_user_id.as_ref()],
+            ctx.program_id,
+        );
+
+        emit_allowance_updated(allowance, vault, vault_bump);
+
+        Ok(())
+    }
+
+    pub fn migrate_legacy_allowance(ctx: Context<MigrateLegacyAllowance>) -> Result<()> {
+        let legacy_allowance = &ctx.accounts.legacy_allowance;
+        let allowance = &mut ctx.accounts.allowance;
+
+        allowance.authority = ctx.accounts.vault.authority;
+        allowance.hashed_user_id = ctx.accounts.vault.hashed_user_id;
+        allowance.spender = ctx.accounts.config.relayer;
+        allowance.cap = legacy_allowance.cap;
+        allowance.remaining = legacy_allowance.remaining;
+
+        emit_allowance_updated(allowance, ctx.accounts.vault.key(), ctx.bumps.vault);
+
+        Ok(())
+    }
+
+    pub fn migrate_relayer_allowance(ctx: Context<MigrateRelayerAllowance>) -> Result<()> {
+        let previous_allowance = &ctx.accounts.previous_allowance;
+        let allowance = &mut ctx.accounts.allowance;
+
+        allowance.authority = previous_allowance.authority;
+        allowance.hashed_user_id = previous_allowance.hashed_user_id;
+        allowance.spender = ctx.accounts.config.relayer;
+        allowance.cap = previous_allowance.cap;
+        allowance.remaining = previous_allowance.remaining;
+        allowance.period_seconds = previous_allowance.period_seconds;
+        allowance.period_start = previous_allowance.period_start;
+        allowance.expires_at = previous_allowance.expires_at;
+        allowance.max_per_tip = previous_allowance.max_per_tip;
+        allowance.has_recipient_rules = previous_allowance.has_recipient_rules;
+
+        if previous_allowance.has_recipient_rules {
+            let previous_rules = ctx
+                .accounts
+                .previous_recipient_rules
+                .as_ref()
+                .ok_or(TipError::MissingRecipientRules)?;
+            let recipient_rules = ctx
+                .accounts
+                .recipient_rules
+                .as_mut()
+                .ok_or(TipError::MissingRecipientRules)?;
+            recipient_rules.allowance = allowance.key();
+            recipient_rules.mode = previous_rules.mode;
+            recipient_rules.recipients = previous_rules.recipients.clone();
+            recipient_rules.bump = ctx
+                .bumps
+                .recipient_rules
+                .ok_or(TipError::MissingRecipientRules)?;
+        }
 
         let (vault, vault_bump) = Pubkey::find_program_address(
             &[b"vault", allowance.hashed_user_id.as_ref()],
             ctx.program_id,
         );
 
-        emit!(AllowanceUpdated {
+        emit_allowance_updated(allowance, vault, vault_bump);
+
+        Ok(())
+    }
+
+    pub fn add_recipient_rule(
+        ctx: Context<AddRecipientRule>,
+        mode: RecipientRuleMode,
+        recipient_hashed_user_id: [u8; 32],
+    ) -> Result<()> {
+        require!(
+            recipient_hashed_user_id.iter().any(|byte| *byte != 0),
+            TipError::InvalidHashedUserId
+        );
+
+        let allowance = &mut ctx.accounts.allowance;
+        let recipient_rules = &mut ctx.accounts.recipient_rules;
+
+        if recipient_rules.allowance == Pubkey::default() {
+            recipient_rules.allowance = allowance.key();
+            recipient_rules.mode = mode;
+            recipient_rules.bump = ctx.bumps.recipient_rules;
+        } else {
+            require!(
+                recipient_rules.mode == mode,
+                TipError::RecipientRuleModeMismatch
+            );
+        }
+
+        if !recipient_rules
+            .recipients
+            .contains(&recipient_hashed_user_id)
+        {
+            require!(
+                recipient_rules.recipients.len() < RecipientRules::MAX_RECIPIENTS,
+                TipError::TooManyRecipientRules
+            );
+            recipient_rules.recipients.push(recipient_hashed_user_id);
+        }
+
+        allowance.has_recipient_rules = true;
+
+        emit!(RecipientRulesUpdated {
             authority: allowance.authority,
-            vault,
-            vault_bump,
+            spender: allowance.spender,
+            allowance: allowance.key(),
+            hashed_user_id: allowance.hashed_user_id,
+            enabled: true,
+            mode: recipient_rules.mode,
+            recipients: recipient_rules.recipients.clone(),
+        });
+
+        Ok(())
+    }
+
+    pub fn remove_recipient_rule(
+        ctx: Context<RemoveRecipientRule>,
+        recipient_hashed_user_id: [u8; 32],
+    ) -> Result<()> {
+        let allowance = &ctx.accounts.allowance;
+        let recipient_rules = &mut ctx.accounts.recipient_rules;
+
+        recipient_rules
+            .recipients
+            .retain(|recipient| *recipient != recipient_hashed_user_id);
+
+        emit!(RecipientRulesUpdated {
+            authority: allowance.authority,
+            spender: allowance.spender,
+            allowance: allowance.key(),
+            hashed_user_id: allowance.hashed_user_id,
+            enabled: true,
+            mode: recipient_rules.mode,
+            recipients: recipient_rules.recipients.clone(),
+        });
+
+        Ok(())
+    }
+
+    pub fn clear_recipient_rules(ctx: Context<ClearRecipientRules>) -> Result<()> {
+        let allowance = &mut ctx.accounts.allowance;
+        allowance.has_recipient_rules = false;
+
+        emit!(RecipientRulesUpdated {
+            authority: allowance.authority,
+            spender: allowance.spender,
+            allowance: allowance.key(),
             hashed_user_id: allowance.hashed_user_id,
-            cap: allowance.cap,
-            remaining: allowance.remaining,
+            enabled: false,
+            mode: ctx.accounts.recipient_rules.mode,
+            recipients: Vec::new(),
         });
 
         Ok(())
@@ -198,17 +472,32 @@ pub mod tipcoin {
         sender_nonce: u64,
         memo: Option<String>,
         recipient_hashed_user_id: [u8; 32],
+        valid_until: i64,
     ) -> Result<()> {
         require!(amount > 0, TipError::InvalidTipAmount);
-        require_keys_eq!(
-            ctx.accounts.relayer.key(),
-            ctx.accounts.config.relayer,
-            TipError::InvalidRelayer
-        );
         require!(
             recipient_hashed_user_id.iter().any(|byte| *byte != 0),
             TipError::InvalidHashedUserId
         );
+        require!(
+            memo.as_ref().map_or(0, String::len) <= MAX_MEMO_LEN,
+            TipError::MemoTooLong
+        );
+        require!(
+            ctx.accounts.config.tip_dispute_window_seconds == 0,
+            TipError::TipEscrowRequired
+        );
+
+        let now = Clock::get()?.unix_timestamp;
+        require!(valid_until == 0 || now <= valid_until, TipError::TipExpired);
+        record_tip_receipt(
+            &mut ctx.accounts.tip_receipt,
+            tip_id,
+            ctx.accounts.sender_vault.key(),
+            ctx.accounts.relayer.key(),
+            now,
+            ctx.bumps.tip_receipt,
+        )?;
 
         let config_token_mint = ctx.accounts.config.token_mint;
         let sender_vault = &mut ctx.accounts.sender_vault;
@@ -234,36 +523,45 @@ pub mod tipcoin {
             TipError::InvalidSenderPda
         );
 
-        if recipient_vault.hashed_user_id == [0u8; 32]
-            && recipient_vault.token_mint == Pubkey::default()
-        {
-            recipient_vault.authority = unclaimed_authority;
-            recipient_vault.hashed_user_id = recipient_hashed_user_id;
-            recipient_vault.token_mint = config_token_mint;
-            recipient_vault.claimed = false;
-        } else {
-            require!(
-                recipient_vault.hashed_user_id == recipient_hashed_user_id,
-                TipError::InvalidRecipientPda
-            );
-            require_keys_eq!(
-                recipient_vault.token_mint,
-                config_token_mint,
-                TipError::InvalidTokenMint
-            );
-        }
+        consume_tip_rate_limit(sender_vault, &ctx.accounts.config, 1, now)?;
 
-        if !recipient_vault.claimed {
-            require_keys_eq!(
-                recipient_vault.authority,
-                unclaimed_authority,
-                TipError::InvalidRecipientPda
-            );
+        if sender_vault.require_tip_signature {
+            let intent = TipIntent {
+                sender_vault: sender_vault.key(),
+                spender: ctx.accounts.relayer.key(),
+                recipient_hashed_user_id,
+                amount,
+                tip_id,
+                sender_nonce,
+                valid_until,
+            };
+            verify_tip_intent(
+                ctx.accounts.instructions_sysvar.as_ref(),
+                &sender_vault.authority,
+                TIP_INTENT_DOMAIN,
+                valid_until,
+                &intent,
+            )?;
         }
 
+        let recipient_vault_created = init_or_verify_recipient_vault(
+            recipient_vault,
+            recipient_hashed_user_id,
+            config_token_mint,
+            unclaimed_authority,
+        )?;
+        check_min_tip_amount(&ctx.accounts.config, amount, recipient_vault_created)?;
+
         let sender_hash = sender_vault.hashed_user_id;
         let recipient_hash = recipient_vault.hashed_user_id;
 
+        check_allowance_recipient(
+            sender_allowance,
+            ctx.accounts.recipient_rules.as_deref(),
+            &recipient_hash,
+            amount,
+        )?;
+
         let (expected_sender_vault, sender_vault_bump) =
             Pubkey::find_program_address(&[b"vault", sender_hash.as_ref()], ctx.program_id);
         require_keys_eq!(
@@ -280,22 +578,12 @@ pub mod tipcoin {
             TipError::InvalidRecipientPda
         );
 
-        if fee_vault.config == Pubkey::default() {
-            fee_vault.config = ctx.accounts.config.key();
-            fee_vault.token_mint = config_token_mint;
-            fee_vault.bump = ctx.bumps.fee_vault;
-        } else {
-            require_keys_eq!(
-                fee_vault.config,
-                ctx.accounts.config.key(),
-                TipError::InvalidFeeVault
-            );
-            require_keys_eq!(
-                fee_vault.token_mint,
-                config_token_mint,
-                TipError::InvalidTokenMint
-            );
-        }
+        init_or_verify_fee_vault(
+            fee_vault,
+            ctx.accounts.config.key(),
+            config_token_mint,
+            ctx.bumps.fee_vault,
+        )?;
 
         let fee_bps = ctx.accounts.config.fee_bps;
         let fee_amount = calculate_fee(amount, fee_bps)?;
@@ -303,52 +591,37 @@ pub mod tipcoin {
             .checked_add(fee_amount)
             .ok_or(TipError::FeeCalculationOverflow)?;
 
-        require!(
-            sender_allowance.remaining >= total_amount,
-            TipError::AllowanceExceeded
-        );
-
-        let sender_hash_slice = sender_hash.as_ref();
-        let sender_vault_bump_seed = [sender_vault_bump];
-        let sender_vault_seeds: [&[u8]; 3] =
-            [b"vault", sender_hash_slice, sender_vault_bump_seed.as_ref()];
-        let signer_seeds: [&[&[u8]]; 1] = [&sender_vault_seeds];
-
-        let transfer_accounts = Transfer {
-            from: ctx.accounts.sender_vault_token_account.to_account_info(),
-            to: ctx.accounts.recipient_vault_token_account.to_account_info(),
-            authority: sender_vault.to_account_info(),
-        };
+        spend_allowance(
+            sender_allowance,
+            sender_vault,
+            sender_vault_bump,
+            sender_nonce,
+            total_amount,
+            now,
+        )?;
 
-        let cpi_ctx = CpiContext::new_with_signer(
+        transfer_from_vault(
             ctx.accounts.token_program.to_account_info(),
-            transfer_accounts,
-            &signer_seeds,
-        );
-
-        token::transfer(cpi_ctx, amount)?;
+            ctx.accounts.sender_vault_token_account.to_account_info(),
+            ctx.accounts.recipient_vault_token_account.to_account_info(),
+            sender_vault.to_account_info(),
+            &sender_hash,
+            sender_vault_bump,
+            amount,
+        )?;
 
         if fee_amount > 0 {
-            let fee_transfer_accounts = Transfer {
-                from: ctx.accounts.sender_vault_token_account.to_account_info(),
-                to: ctx.accounts.fee_vault_token_account.to_account_info(),
-                authority: sender_vault.to_account_info(),
-            };
-
-            let fee_cpi_ctx = CpiContext::new_with_signer(
+            transfer_from_vault(
                 ctx.accounts.token_program.to_account_info(),
-                fee_transfer_accounts,
-                &signer_seeds,
-            );
-
-            token::transfer(fee_cpi_ctx, fee_amount)?;
+                ctx.accounts.sender_vault_token_account.to_account_info(),
+                ctx.accounts.fee_vault_token_account.to_account_info(),
+                sender_vault.to_account_info(),
+                &sender_hash,
+                sender_vault_bump,
+                fee_amount,
+            )?;
         }
 
-        sender_allowance.remaining = sender_allowance
-            .remaining
-            .checked_sub(total_amount)
-            .ok_or(TipError::AllowanceExceeded)?;
-
         emit!(TipEvent {
             relayer: ctx.accounts.relayer.key(),
             sender_vault: sender_vault.key(),
@@ -366,466 +639,5870 @@ pub mod tipcoin {
             fee_amount,
             fee_bps,
             total_amount,
+            memo,
         });
 
-        let _ = memo;
-
         Ok(())
     }
 
-    pub fn withdraw(ctx: Context<Withdraw>, amount: u64) -> Result<()> {
-        require!(amount > 0, TipError::InvalidWithdrawAmount);
-
-        require_keys_eq!(
-            ctx.accounts.vault.authority,
-            ctx.accounts.authority.key(),
-            TipError::InvalidVaultAuthority
+    pub fn escrow_tip(
+        ctx: Context<EscrowTip>,
+        amount: u64,
+        tip_id: [u8; 32],
+        sender_nonce: u64,
+        memo: Option<String>,
+        recipient_hashed_user_id: [u8; 32],
+        valid_until: i64,
+    ) -> Result<()> {
+        require!(amount > 0, TipError::InvalidTipAmount);
+        require!(
+            recipient_hashed_user_id.iter().any(|byte| *byte != 0),
+            TipError::InvalidHashedUserId
+        );
+        require!(
+            memo.as_ref().map_or(0, String::len) <= MAX_MEMO_LEN,
+            TipError::MemoTooLong
         );
 
+        let dispute_window_seconds = ctx.accounts.config.tip_dispute_window_seconds;
+        require!(dispute_window_seconds > 0, TipError::TipEscrowDisabled);
+
+        let now = Clock::get()?.unix_timestamp;
+        require!(valid_until == 0 || now <= valid_until, TipError::TipExpired);
+        record_tip_receipt(
+            &mut ctx.accounts.tip_receipt,
+            tip_id,
+            ctx.accounts.sender_vault.key(),
+            ctx.accounts.relayer.key(),
+            now,
+            ctx.bumps.tip_receipt,
+        )?;
+
+        let config_key = ctx.accounts.config.key();
+        let config_token_mint = ctx.accounts.config.token_mint;
+        let sender_vault = &mut ctx.accounts.sender_vault;
+        let sender_allowance = &mut ctx.accounts.sender_allowance;
+        let recipient_vault = &mut ctx.accounts.recipient_vault;
+        let unclaimed_authority = unclaimed_authority_key(ctx.program_id);
+
         require_keys_eq!(
-            ctx.accounts.vault.token_mint,
-            ctx.accounts.config.token_mint,
+            sender_vault.token_mint,
+            config_token_mint,
             TipError::InvalidTokenMint
         );
-
-        require!(
-            ctx.accounts.vault_token_account.amount >= amount,
-            TipError::InsufficientVaultBalance
+        require_keys_eq!(
+            sender_vault.authority,
+            sender_allowance.authority,
+            TipError::InvalidAuthority
         );
 
-        let vault_bump = ctx.bumps.vault;
-        let vault_seeds: [&[u8]; 3] = [
-            b"vault",
-            ctx.accounts.vault.hashed_user_id.as_ref(),
-            &[vault_bump],
-        ];
-        let signer_seeds: [&[&[u8]]; 1] = [&vault_seeds];
+        consume_tip_rate_limit(sender_vault, &ctx.accounts.config, 1, now)?;
 
-        let cpi_accounts = Transfer {
-            from: ctx.accounts.vault_token_account.to_account_info(),
-            to: ctx.accounts.destination_token_account.to_account_info(),
-            authority: ctx.accounts.vault.to_account_info(),
-        };
+        if sender_vault.require_tip_signature {
+            let intent = TipIntent {
+                sender_vault: sender_vault.key(),
+                spender: ctx.accounts.relayer.key(),
+                recipient_hashed_user_id,
+                amount,
+                tip_id,
+                sender_nonce,
+                valid_until,
+            };
+            verify_tip_intent(
+                ctx.accounts.instructions_sysvar.as_ref(),
+                &sender_vault.authority,
+                TIP_INTENT_DOMAIN,
+                valid_until,
+                &intent,
+            )?;
+        }
 
-        let cpi_ctx = CpiContext::new_with_signer(
-            ctx.accounts.token_program.to_account_info(),
-            cpi_accounts,
-            &signer_seeds,
-        );
+        let recipient_vault_created = init_or_verify_recipient_vault(
+            recipient_vault,
+            recipient_hashed_user_id,
+            config_token_mint,
+            unclaimed_authority,
+        )?;
+        check_min_tip_amount(&ctx.accounts.config, amount, recipient_vault_created)?;
 
-        token::transfer(cpi_ctx, amount)?;
+        let sender_hash = sender_vault.hashed_user_id;
+        let recipient_hash = recipient_vault.hashed_user_id;
 
-        emit!(WithdrawEvent {
-            authority: ctx.accounts.authority.key(),
-            vault: ctx.accounts.vault.key(),
-            vault_bump,
-            hashed_user_id: ctx.accounts.vault.hashed_user_id,
-            destination: ctx.accounts.destination_token_account.owner,
-            destination_token_account: ctx.accounts.destination_token_account.key(),
+        check_allowance_recipient(
+            sender_allowance,
+            ctx.accounts.recipient_rules.as_deref(),
+            &recipient_hash,
+            amount,
+        )?;
+
+        init_or_verify_fee_vault(
+            &mut ctx.accounts.fee_vault,
+            config_key,
+            config_token_mint,
+            ctx.bumps.fee_vault,
+        )?;
+        init_or_verify_tip_escrow(
+            &mut ctx.accounts.tip_escrow,
+            config_key,
+            config_token_mint,
+            ctx.bumps.tip_escrow,
+        )?;
+
+        let sender_vault_bump = ctx.bumps.sender_vault;
+        let fee_bps = ctx.accounts.config.fee_bps;
+        let fee_amount = calculate_fee(amount, fee_bps)?;
+        let total_amount = amount
+            .checked_add(fee_amount)
+            .ok_or(TipError::FeeCalculationOverflow)?;
+
+        spend_allowance(
+            sender_allowance,
+            sender_vault,
+            sender_vault_bump,
+            sender_nonce,
+            total_amount,
+            now,
+        )?;
+
+        transfer_from_vault(
+            ctx.accounts.token_program.to_account_info(),
+            ctx.accounts.sender_vault_token_account.to_account_info(),
+            ctx.accounts.tip_escrow_token_account.to_account_info(),
+            sender_vault.to_account_info(),
+            &sender_hash,
+            sender_vault_bump,
+            total_amount,
+        )?;
+
+        let finalizable_at = now
+            .checked_add(dispute_window_seconds)
+            .ok_or(TipError::InvalidTimestamp)?;
+
+        let pending_tip = &mut ctx.accounts.pending_tip;
+        pending_tip.tip_id = tip_id;
+        pending_tip.relayer = ctx.accounts.relayer.key();
+        pending_tip.sender_vault = sender_vault.key();
+        pending_tip.sender_allowance = sender_allowance.key();
+        pending_tip.recipient_vault = recipient_vault.key();
+        pending_tip.sender_hashed_user_id = sender_hash;
+        pending_tip.recipient_hashed_user_id = recipient_hash;
+        pending_tip.amount = amount;
+        pending_tip.fee_amount = fee_amount;
+        pending_tip.created_at = now;
+        pending_tip.finalizable_at = finalizable_at;
+        pending_tip.bump = ctx.bumps.pending_tip;
+
+        emit!(TipEscrowed {
+            relayer: pending_tip.relayer,
+            pending_tip: pending_tip.key(),
+            sender_vault: pending_tip.sender_vault,
+            recipient_vault: pending_tip.recipient_vault,
+            sender_hashed_user_id: sender_hash,
+            recipient_hashed_user_id: recipient_hash,
             amount,
+            sender_nonce,
+            allowance_remaining: sender_allowance.remaining,
+            tip_id,
+            fee_amount,
+            fee_bps,
+            total_amount,
+            finalizable_at,
+            memo,
         });
 
         Ok(())
     }
 
-    pub fn withdraw_fee(ctx: Context<WithdrawFee>, amount: u64) -> Result<()> {
-        require!(amount > 0, TipError::InvalidWithdrawAmount);
-
+    pub fn finalize_tip(ctx: Context<FinalizeTip>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let pending_tip = &ctx.accounts.pending_tip;
         require!(
-            ctx.accounts.fee_vault_token_account.amount >= amount,
-            TipError::InsufficientVaultBalance
+            now >= pending_tip.finalizable_at,
+            TipError::TipDisputeWindowOpen
         );
 
-        let fee_vault_bump = ctx.accounts.fee_vault.bump;
         let config_key = ctx.accounts.config.key();
-        let fee_vault_seeds: [&[u8]; 3] = [b"fee_vault", config_key.as_ref(), &[fee_vault_bump]];
-        let signer_seeds: [&[&[u8]]; 1] = [&fee_vault_seeds];
-
-        let cpi_accounts = Transfer {
-            from: ctx.accounts.fee_vault_token_account.to_account_info(),
-            to: ctx.accounts.destination_token_account.to_account_info(),
-            authority: ctx.accounts.fee_vault.to_account_info(),
-        };
+        let tip_escrow_bump = ctx.accounts.tip_escrow.bump;
 
-        let cpi_ctx = CpiContext::new_with_signer(
+        transfer_from_tip_escrow(
             ctx.accounts.token_program.to_account_info(),
-            cpi_accounts,
-            &signer_seeds,
-        );
+            ctx.accounts.tip_escrow_token_account.to_account_info(),
+            ctx.accounts.recipient_vault_token_account.to_account_info(),
+            ctx.accounts.tip_escrow.to_account_info(),
+            &config_key,
+            tip_escrow_bump,
+            pending_tip.amount,
+        )?;
 
-        token::transfer(cpi_ctx, amount)?;
+        if pending_tip.fee_amount > 0 {
+            transfer_from_tip_escrow(
+                ctx.accounts.token_program.to_account_info(),
+                ctx.accounts.tip_escrow_token_account.to_account_info(),
+                ctx.accounts.fee_vault_token_account.to_account_info(),
+                ctx.accounts.tip_escrow.to_account_info(),
+                &config_key,
+                tip_escrow_bump,
+                pending_tip.fee_amount,
+            )?;
+        }
 
-        Ok(())
-    }
+        emit!(TipFinalized {
+            pending_tip: pending_tip.key(),
+            tip_id: pending_tip.tip_id,
+            sender_vault: pending_tip.sender_vault,
+            recipient_vault: pending_tip.recipient_vault,
+            sender_hashed_user_id: pending_tip.sender_hashed_user_id,
+            recipient_hashed_user_id: pending_tip.recipient_hashed_user_id,
+            amount: pending_tip.amount,
+            fee_amount: pending_tip.fee_amount,
+        });
 
-    pub fn set_relayer(ctx: Context<SetRelayer>, new_relayer: Pubkey) -> Result<()> {
-        require!(new_relayer != Pubkey::default(), TipError::InvalidRelayer);
-        ctx.accounts.config.relayer = new_relayer;
         Ok(())
     }
 
-    pub fn set_fee_rate(ctx: Context<SetFeeRate>, fee_bps: u16) -> Result<()> {
-        require!(fee_bps <= MAX_FEE_BPS, TipError::InvalidFeeBps);
-        ctx.accounts.config.fee_bps = fee_bps;
+    pub fn reverse_tip(ctx: Context<ReverseTip>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let config = &ctx.accounts.config;
+        let pending_tip = &ctx.accounts.pending_tip;
+        let authority = ctx.accounts.authority.key();
+
+        require!(
+            authority == pending_tip.relayer
+                || authority == config.relayer
+                || authority == config.claim_authority,
+            TipError::InvalidAuthority
+        );
+        require!(
+            now < pending_tip.finalizable_at,
+            TipError::TipDisputeWindowClosed
+        );
+
+        let total_amount = pending_tip
+            .amount
+            .checked_add(pending_tip.fee_amount)
+            .ok_or(TipError::FeeCalculationOverflow)?;
+
+        transfer_from_tip_escrow(
+            ctx.accounts.token_program.to_account_info(),
+            ctx.accounts.tip_escrow_token_account.to_account_info(),
+            ctx.accounts.sender_vault_token_account.to_account_info(),
+            ctx.accounts.tip_escrow.to_account_info(),
+            &config.key(),
+            ctx.accounts.tip_escrow.bump,
+            total_amount,
+        )?;
+
+        let sender_vault = &mut ctx.accounts.sender_vault;
+        refund_tip_rate_limit(sender_vault, config, 1, now)?;
+        refund_withdraw_limit(sender_vault, total_amount, pending_tip.created_at, now);
+
+        let sender_allowance = &mut ctx.accounts.sender_allowance;
+        if refresh_allowance_window(sender_allowance, now) {
+            emit_allowance_updated(sender_allowance, sender_vault.key(), ctx.bumps.sender_vault);
+        }
+        sender_allowance.remaining = sender_allowance
+            .remaining
+            .saturating_add(total_amount)
+            .min(sender_allowance.cap);
+
+        emit!(TipReversed {
+            pending_tip: pending_tip.key(),
+            reversed_by: authority,
+            tip_id: pending_tip.tip_id,
+            sender_vault: pending_tip.sender_vault,
+            recipient_vault: pending_tip.recipient_vault,
+            sender_hashed_user_id: pending_tip.sender_hashed_user_id,
+            recipient_hashed_user_id: pending_tip.recipient_hashed_user_id,
+            amount: pending_tip.amount,
+            fee_amount: pending_tip.fee_amount,
+            allowance_remaining: sender_allowance.remaining,
+        });
+
         Ok(())
     }
 
-    pub fn set_claim_authority(
-        ctx: Context<SetClaimAuthority>,
-        new_claim_authority: Pubkey,
+    pub fn tip_to_wallet(
+        ctx: Context<TipToWallet>,
+        amount: u64,
+        tip_id: [u8; 32],
+        sender_nonce: u64,
+        memo: Option<String>,
+        valid_until: i64,
     ) -> Result<()> {
+        require!(amount > 0, TipError::InvalidTipAmount);
         require!(
-            new_claim_authority != Pubkey::default(),
-            TipError::InvalidClaimAuthority
+            memo.as_ref().map_or(0, String::len) <= MAX_MEMO_LEN,
+            TipError::MemoTooLong
         );
-        ctx.accounts.config.claim_authority = new_claim_authority;
-        Ok(())
-    }
-}
 
-#[derive(AnchorSerialize, AnchorDeserialize)]
-pub struct InitializeConfigArgs {
-    pub relayer: Pubkey,
-    pub token_mint: Pubkey,
-    pub fee_bps: u16,
-    pub claim_authority: Pubkey,
-}
+        let now = Clock::get()?.unix_timestamp;
+        require!(valid_until == 0 || now <= valid_until, TipError::TipExpired);
+        record_tip_receipt(
+            &mut ctx.accounts.tip_receipt,
+            tip_id,
+            ctx.accounts.sender_vault.key(),
+            ctx.accounts.relayer.key(),
+            now,
+            ctx.bumps.tip_receipt,
+        )?;
 
-#[derive(Accounts)]
-pub struct InitializeConfig<'info> {
-    #[account(mut)]
-    pub upgrade_authority: Signer<'info>,
-    /// CHECK: Verified as the deployed Tipcoin program
-    #[account(address = crate::ID)]
-    pub program: AccountInfo<'info>,
-    /// CHECK: Must be the ProgramData account for `program`
-    #[account(mut)]
-    pub program_data: AccountInfo<'info>,
-    #[account(
-        init,
-        payer = upgrade_authority,
-        space = Config::SPACE,
-        seeds = [b"config"],
-        bump
-    )]
-    pub config: Account<'info, Config>,
-    pub system_program: Program<'info, System>,
-}
+        let config_token_mint = ctx.accounts.config.token_mint;
+        let sender_vault = &mut ctx.accounts.sender_vault;
+        let sender_allowance = &mut ctx.accounts.sender_allowance;
+        let fee_vault = &mut ctx.accounts.fee_vault;
+        let destination_token_account = &ctx.accounts.destination_token_account;
 
-#[derive(Accounts)]
-#[instruction(hashed_user_id: [u8; 32])]
-pub struct ClaimVault<'info> {
-    #[account(mut)]
-    pub authority: Signer<'info>,
-    #[account(mut)]
-    pub claim_authority: Signer<'info>,
-    #[account(
-        seeds = [b"config"],
-        bump,
-        constraint = config.token_mint != Pubkey::default() @ TipError::InvalidTokenMint,
-        constraint = config.claim_authority != Pubkey::default() @ TipError::InvalidClaimAuthority
-    )]
-    pub config: Account<'info, Config>,
-    #[account(
-        init_if_needed,
-        payer = authority,
-        space = Vault::SPACE,
-        seeds = [b"vault", hashed_user_id.as_ref()],
-        bump
-    )]
-    pub vault: Account<'info, Vault>,
-    #[account(
-        init_if_needed,
-        payer = authority,
-        space = Allowance::SPACE,
-        seeds = [b"allowance", hashed_user_id.as_ref()],
-        bump
-    )]
-    pub allowance: Account<'info, Allowance>,
-    pub system_program: Program<'info, System>,
-    #[account(address = sysvar::rent::ID)]
-    pub rent: Sysvar<'info, Rent>,
-    #[account(constraint = token_mint.key() == config.token_mint @ TipError::InvalidTokenMint)]
-    pub token_mint: Account<'info, Mint>,
-}
+        require_keys_eq!(
+            sender_vault.token_mint,
+            config_token_mint,
+            TipError::InvalidTokenMint
+        );
+        require_keys_eq!(
+            sender_vault.authority,
+            sender_allowance.authority,
+            TipError::InvalidAuthority
+        );
 
-#[derive(Accounts)]
-pub struct Deposit<'info> {
-    #[account(seeds = [b"config"], bump)]
-    pub config: Account<'info, Config>,
-    #[account(mut)]
-    pub authority: Signer<'info>,
-    #[account(
-        mut,
-        seeds = [b"vault", vault.hashed_user_id.as_ref()],
-        bump,
-        has_one = authority
-    )]
-    pub vault: Account<'info, Vault>,
-    #[account(
-        mut,
-        constraint = authority_token_account.owner == authority.key() @ TipError::InvalidAuthority,
-        constraint = authority_token_account.mint == config.token_mint @ TipError::InvalidTokenMint
-    )]
-    pub authority_token_account: Account<'info, TokenAccount>,
-    #[account(
-        mut,
-        constraint = vault_token_account.owner == vault.key() @ TipError::InvalidVaultAuthority,
-        constraint = vault_token_account.mint == config.token_mint @ TipError::InvalidTokenMint
-    )]
-    pub vault_token_account: Account<'info, TokenAccount>,
-    pub token_program: Program<'info, Token>,
-}
+        consume_tip_rate_limit(sender_vault, &ctx.accounts.config, 1, now)?;
 
-#[derive(Accounts)]
-pub struct ApproveAllowance<'info> {
-    pub authority: Signer<'info>,
-    #[account(
-        mut,
-        seeds = [b"allowance", allowance.hashed_user_id.as_ref()],
-        bump,
-        has_one = authority
-    )]
-    pub allowance: Account<'info, Allowance>,
-}
+        if sender_vault.require_tip_signature {
+            let intent = WalletTipIntent {
+                sender_vault: sender_vault.key(),
+                spender: ctx.accounts.relayer.key(),
+                destination_token_account: destination_token_account.key(),
+                amount,
+                tip_id,
+                sender_nonce,
+                valid_until,
+            };
+            verify_tip_intent(
+                ctx.accounts.instructions_sysvar.as_ref(),
+                &sender_vault.authority,
+                WALLET_TIP_INTENT_DOMAIN,
+                valid_until,
+                &intent,
+            )?;
+        }
 
-#[derive(Accounts)]
-pub struct RevokeAllowance<'info> {
-    pub authority: Signer<'info>,
-    #[account(
-        mut,
-        seeds = [b"allowance", allowance.hashed_user_id.as_ref()],
-        bump,
-        has_one = authority
-    )]
-    pub allowance: Account<'info, Allowance>,
-}
+        check_min_tip_amount(&ctx.accounts.config, amount, false)?;
+        check_wallet_recipient(sender_allowance, amount)?;
 
-#[derive(Accounts)]
-#[instruction(
-    amount: u64,
-    tip_id: [u8; 32],
-    sender_nonce: u64,
-    memo: Option<String>,
-    recipient_hashed_user_id: [u8; 32]
-)]
-pub struct Tip<'info> {
-    #[account(mut, seeds = [b"config"], bump)]
-    pub config: Account<'info, Config>,
-    #[account(mut)]
-    pub relayer: Signer<'info>,
-    #[account(
-        mut,
-        seeds = [b"vault", sender_vault.hashed_user_id.as_ref()],
-        bump
-    )]
-    pub sender_vault: Account<'info, Vault>,
-    #[account(
-        mut,
-        seeds = [b"allowance", sender_allowance.hashed_user_id.as_ref()],
-        bump
-    )]
-    pub sender_allowance: Account<'info, Allowance>,
-    #[account(
+        init_or_verify_fee_vault(
+            fee_vault,
+            ctx.accounts.config.key(),
+            config_token_mint,
+            ctx.bumps.fee_vault,
+        )?;
+
+        let sender_hash = sender_vault.hashed_user_id;
+        let sender_vault_bump = ctx.bumps.sender_vault;
+        let fee_bps = ctx.accounts.config.fee_bps;
+        let fee_amount = calculate_fee(amount, fee_bps)?;
+        let total_amount = amount
+            .checked_add(fee_amount)
+            .ok_or(TipError::FeeCalculationOverflow)?;
+
+        spend_allowance(
+            sender_allowance,
+            sender_vault,
+            sender_vault_bump,
+            sender_nonce,
+            total_amount,
+            now,
+        )?;
+
+        transfer_from_vault(
+            ctx.accounts.token_program.to_account_info(),
+            ctx.accounts.sender_vault_token_account.to_account_info(),
+            destination_token_account.to_account_info(),
+            sender_vault.to_account_info(),
+            &sender_hash,
+            sender_vault_bump,
+            amount,
+        )?;
+
+        if fee_amount > 0 {
+            transfer_from_vault(
+                ctx.accounts.token_program.to_account_info(),
+                ctx.accounts.sender_vault_token_account.to_account_info(),
+                ctx.accounts.fee_vault_token_account.to_account_info(),
+                sender_vault.to_account_info(),
+                &sender_hash,
+                sender_vault_bump,
+                fee_amount,
+            )?;
+        }
+
+        emit!(WalletTipEvent {
+            relayer: ctx.accounts.relayer.key(),
+            sender_vault: sender_vault.key(),
+            sender_vault_bump,
+            sender_hashed_user_id: sender_hash,
+            destination_token_account: destination_token_account.key(),
+            destination_owner: destination_token_account.owner,
+            amount,
+            sender_nonce,
+            allowance_remaining: sender_allowance.remaining,
+            tip_id,
+            fee_vault: fee_vault.key(),
+            fee_vault_bump: fee_vault.bump,
+            fee_amount,
+            fee_bps,
+            total_amount,
+            memo,
+        });
+
+        Ok(())
+    }
+
+    pub fn tip_many<'info>(
+        ctx: Context<'_, '_, 'info, 'info, TipMany<'info>>,
+        recipients: Vec<TipManyRecipient>,
+        tip_id: [u8; 32],
+        sender_nonce: u64,
+        memo: Option<String>,
+        valid_until: i64,
+    ) -> Result<()> {
+        require!(
+            !recipients.is_empty() && recipients.len() <= MAX_TIP_MANY_RECIPIENTS,
+            TipError::InvalidRecipientCount
+        );
+        require!(
+            ctx.remaining_accounts.len() == recipients.len() * 2,
+            TipError::InvalidRecipientCount
+        );
+        require!(
+            memo.as_ref().map_or(0, String::len) <= MAX_MEMO_LEN,
+            TipError::MemoTooLong
+        );
+
+        let now = Clock::get()?.unix_timestamp;
+        require!(valid_until == 0 || now <= valid_until, TipError::TipExpired);
+        record_tip_receipt(
+            &mut ctx.accounts.tip_receipt,
+            tip_id,
+            ctx.accounts.sender_vault.key(),
+            ctx.accounts.relayer.key(),
+            now,
+            ctx.bumps.tip_receipt,
+        )?;
+
+        let config_key = ctx.accounts.config.key();
+        let config_token_mint = ctx.accounts.config.token_mint;
+        let fee_bps = ctx.accounts.config.fee_bps;
+        let sender_vault = &mut ctx.accounts.sender_vault;
+        let sender_allowance = &mut ctx.accounts.sender_allowance;
+        let unclaimed_authority = unclaimed_authority_key(ctx.program_id);
+
+        require_keys_eq!(
+            sender_vault.token_mint,
+            config_token_mint,
+            TipError::InvalidTokenMint
+        );
+        require_keys_eq!(
+            sender_vault.authority,
+            sender_allowance.authority,
+            TipError::InvalidAuthority
+        );
+
+        consume_tip_rate_limit(
+            sender_vault,
+            &ctx.accounts.config,
+            recipients.len() as u64,
+            now,
+        )?;
+
+        if sender_vault.require_tip_signature {
+            let intent = TipManyIntent {
+                sender_vault: sender_vault.key(),
+                spender: ctx.accounts.relayer.key(),
+                recipients: recipients
+                    .iter()
+                    .map(|recipient| TipIntentRecipient {
+                        recipient_hashed_user_id: recipient.recipient_hashed_user_id,
+                        amount: recipient.amount,
+                    })
+                    .collect(),
+                tip_id,
+                sender_nonce,
+                valid_until,
+            };
+            verify_tip_intent(
+                ctx.accounts.instructions_sysvar.as_ref(),
+                &sender_vault.authority,
+                TIP_MANY_INTENT_DOMAIN,
+                valid_until,
+                &intent,
+            )?;
+        }
+
+        init_or_verify_fee_vault(
+            &mut ctx.accounts.fee_vault,
+            config_key,
+            config_token_mint,
+            ctx.bumps.fee_vault,
+        )?;
+
+        let sender_hash = sender_vault.hashed_user_id;
+        let sender_vault_bump = ctx.bumps.sender_vault;
+
+        let mut tip_amounts = Vec::with_capacity(recipients.len());
+        let mut batch_fee_amount: u64 = 0;
+        let mut batch_total_amount: u64 = 0;
+        for (index, recipient) in recipients.iter().enumerate() {
+            require!(recipient.amount > 0, TipError::InvalidTipAmount);
+            require!(
+                recipients[..index].iter().all(|other| {
+                    other.recipient_hashed_user_id != recipient.recipient_hashed_user_id
+                }),
+                TipError::DuplicateRecipient
+            );
+            check_allowance_recipient(
+                sender_allowance,
+                ctx.accounts.recipient_rules.as_deref(),
+                &recipient.recipient_hashed_user_id,
+                recipient.amount,
+            )?;
+
+            let fee_amount = calculate_fee(recipient.amount, fee_bps)?;
+            let total_amount = recipient
+                .amount
+                .checked_add(fee_amount)
+                .ok_or(TipError::FeeCalculationOverflow)?;
+            batch_fee_amount = batch_fee_amount
+                .checked_add(fee_amount)
+                .ok_or(TipError::FeeCalculationOverflow)?;
+            batch_total_amount = batch_total_amount
+                .checked_add(total_amount)
+                .ok_or(TipError::FeeCalculationOverflow)?;
+            tip_amounts.push((fee_amount, total_amount));
+        }
+
+        spend_allowance(
+            sender_allowance,
+            sender_vault,
+            sender_vault_bump,
+            sender_nonce,
+            batch_total_amount,
+            now,
+        )?;
+
+        for (index, recipient) in recipients.iter().enumerate() {
+            let recipient_vault_info = &ctx.remaining_accounts[index * 2];
+            let recipient_vault_token_info = &ctx.remaining_accounts[index * 2 + 1];
+            let recipient_hash = recipient.recipient_hashed_user_id;
+
+            require!(
+                recipient_hash.iter().any(|byte| *byte != 0),
+                TipError::InvalidHashedUserId
+            );
+
+            let recipient_vault_created = load_or_create_recipient_vault(
+                recipient_vault_info,
+                &ctx.accounts.relayer,
+                &ctx.accounts.system_program,
+                recipient_hash,
+                recipient.recipient_vault_bump,
+                config_token_mint,
+                unclaimed_authority,
+                ctx.program_id,
+            )?;
+            check_min_tip_amount(
+                &ctx.accounts.config,
+                recipient.amount,
+                recipient_vault_created,
+            )?;
+
+            let recipient_vault_token_account =
+                Account::<TokenAccount>::try_from(recipient_vault_token_info)?;
+            require_keys_eq!(
+                recipient_vault_token_account.owner,
+                recipient_vault_info.key(),
+                TipError::InvalidVaultAuthority
+            );
+            require_keys_eq!(
+                recipient_vault_token_account.mint,
+                config_token_mint,
+                TipError::InvalidTokenMint
+            );
+
+            transfer_from_vault(
+                ctx.accounts.token_program.to_account_info(),
+                ctx.accounts.sender_vault_token_account.to_account_info(),
+                recipient_vault_token_info.clone(),
+                sender_vault.to_account_info(),
+                &sender_hash,
+                sender_vault_bump,
+                recipient.amount,
+            )?;
+
+            let (fee_amount, total_amount) = tip_amounts[index];
+            emit!(TipEvent {
+                relayer: ctx.accounts.relayer.key(),
+                sender_vault: sender_vault.key(),
+                sender_vault_bump,
+                recipient_vault: recipient_vault_info.key(),
+                recipient_vault_bump: recipient.recipient_vault_bump,
+                sender_hashed_user_id: sender_hash,
+                recipient_hashed_user_id: recipient_hash,
+                amount: recipient.amount,
+                sender_nonce,
+                allowance_remaining: sender_allowance.remaining,
+                tip_id,
+                fee_vault: ctx.accounts.fee_vault.key(),
+                fee_vault_bump: ctx.accounts.fee_vault.bump,
+                fee_amount,
+                fee_bps,
+                total_amount,
+                memo: memo.clone(),
+            });
+        }
+
+        if batch_fee_amount > 0 {
+            transfer_from_vault(
+                ctx.accounts.token_program.to_account_info(),
+                ctx.accounts.sender_vault_token_account.to_account_info(),
+                ctx.accounts.fee_vault_token_account.to_account_info(),
+                sender_vault.to_account_info(),
+                &sender_hash,
+                sender_vault_bump,
+                batch_fee_amount,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    pub fn create_session(
+        ctx: Context<CreateSession>,
+        session_key: Pubkey,
+        cap: u64,
+        expires_at: i64,
+    ) -> Result<()> {
+        require!(
+            session_key != Pubkey::default(),
+            TipError::InvalidSessionKey
+        );
+
+        let now = Clock::get()?.unix_timestamp;
+        require!(
+            expires_at > now && expires_at - now <= MAX_SESSION_SECONDS,
+            TipError::InvalidSessionExpiry
+        );
+
+        let vault = &ctx.accounts.vault;
+        let session = &mut ctx.accounts.session;
+
+        session.vault = vault.key();
+        session.authority = vault.authority;
+        session.session_key = session_key;
+        session.cap = cap;
+        session.remaining = cap;
+        session.expires_at = expires_at;
+        session.bump = ctx.bumps.session;
+
+        emit!(SessionCreated {
+            authority: session.authority,
+            vault: session.vault,
+            hashed_user_id: vault.hashed_user_id,
+            session: session.key(),
+            session_key,
+            cap,
+            expires_at,
+        });
+
+        Ok(())
+    }
+
+    pub fn revoke_session(ctx: Context<RevokeSession>) -> Result<()> {
+        let session = &ctx.accounts.session;
+
+        emit!(SessionRevoked {
+            authority: session.authority,
+            vault: session.vault,
+            hashed_user_id: ctx.accounts.vault.hashed_user_id,
+            session: session.key(),
+            session_key: session.session_key,
+            remaining: session.remaining,
+        });
+
+        Ok(())
+    }
+
+    pub fn tip_with_session(
+        ctx: Context<TipWithSession>,
+        amount: u64,
+        tip_id: [u8; 32],
+        sender_nonce: u64,
+        memo: Option<String>,
+        recipient_hashed_user_id: [u8; 32],
+        valid_until: i64,
+    ) -> Result<()> {
+        require!(amount > 0, TipError::InvalidTipAmount);
+        require!(
+            recipient_hashed_user_id.iter().any(|byte| *byte != 0),
+            TipError::InvalidHashedUserId
+        );
+        require!(
+            memo.as_ref().map_or(0, String::len) <= MAX_MEMO_LEN,
+            TipError::MemoTooLong
+        );
+
+        let now = Clock::get()?.unix_timestamp;
+        require!(valid_until == 0 || now <= valid_until, TipError::TipExpired);
+        record_tip_receipt(
+            &mut ctx.accounts.tip_receipt,
+            tip_id,
+            ctx.accounts.sender_vault.key(),
+            ctx.accounts.session_key.key(),
+            now,
+            ctx.bumps.tip_receipt,
+        )?;
+
+        let config_token_mint = ctx.accounts.config.token_mint;
+        let sender_vault = &mut ctx.accounts.sender_vault;
+        let session = &mut ctx.accounts.session;
+        let recipient_vault = &mut ctx.accounts.recipient_vault;
+        let fee_vault = &mut ctx.accounts.fee_vault;
+        let unclaimed_authority = unclaimed_authority_key(ctx.program_id);
+
+        require_keys_eq!(
+            sender_vault.token_mint,
+            config_token_mint,
+            TipError::InvalidTokenMint
+        );
+        require_keys_eq!(
+            sender_vault.authority,
+            session.authority,
+            TipError::InvalidAuthority
+        );
+
+        consume_tip_rate_limit(sender_vault, &ctx.accounts.config, 1, now)?;
+
+        require!(now < session.expires_at, TipError::SessionExpired);
+        consume_sender_nonce(&mut session.sender_nonces, sender_nonce)?;
+
+        let recipient_vault_created = init_or_verify_recipient_vault(
+            recipient_vault,
+            recipient_hashed_user_id,
+            config_token_mint,
+            unclaimed_authority,
+        )?;
+        check_min_tip_amount(&ctx.accounts.config, amount, recipient_vault_created)?;
+
+        init_or_verify_fee_vault(
+            fee_vault,
+            ctx.accounts.config.key(),
+            config_token_mint,
+            ctx.bumps.fee_vault,
+        )?;
+
+        let sender_hash = sender_vault.hashed_user_id;
+        let sender_vault_bump = ctx.bumps.sender_vault;
+        let recipient_vault_bump = ctx.bumps.recipient_vault;
+
+        let fee_bps = ctx.accounts.config.fee_bps;
+        let fee_amount = calculate_fee(amount, fee_bps)?;
+        let total_amount = amount
+            .checked_add(fee_amount)
+            .ok_or(TipError::FeeCalculationOverflow)?;
+
+        session.remaining = session
+            .remaining
+            .checked_sub(total_amount)
+            .ok_or(TipError::SessionBudgetExceeded)?;
+        consume_withdraw_limit(sender_vault, total_amount, now)?;
+
+        transfer_from_vault(
+            ctx.accounts.token_program.to_account_info(),
+            ctx.accounts.sender_vault_token_account.to_account_info(),
+            ctx.accounts.recipient_vault_token_account.to_account_info(),
+            sender_vault.to_account_info(),
+            &sender_hash,
+            sender_vault_bump,
+            amount,
+        )?;
+
+        if fee_amount > 0 {
+            transfer_from_vault(
+                ctx.accounts.token_program.to_account_info(),
+                ctx.accounts.sender_vault_token_account.to_account_info(),
+                ctx.accounts.fee_vault_token_account.to_account_info(),
+                sender_vault.to_account_info(),
+                &sender_hash,
+                sender_vault_bump,
+                fee_amount,
+            )?;
+        }
+
+        emit!(TipEvent {
+            relayer: ctx.accounts.session_key.key(),
+            sender_vault: sender_vault.key(),
+            sender_vault_bump,
+            recipient_vault: recipient_vault.key(),
+            recipient_vault_bump,
+            sender_hashed_user_id: sender_hash,
+            recipient_hashed_user_id,
+            amount,
+            sender_nonce,
+            allowance_remaining: session.remaining,
+            tip_id,
+            fee_vault: fee_vault.key(),
+            fee_vault_bump: fee_vault.bump,
+            fee_amount,
+            fee_bps,
+            total_amount,
+            memo,
+        });
+
+        Ok(())
+    }
+
+    pub fn create_subscription(
+        ctx: Context<CreateSubscription>,
+        recipient_hashed_user_id: [u8; 32],
+        amount: u64,
+        interval_seconds: i64,
+        first_due: i64,
+        periods: u64,
+    ) -> Result<()> {
+        require!(amount > 0, TipError::InvalidTipAmount);
+        require!(
+            recipient_hashed_user_id.iter().any(|byte| *byte != 0),
+            TipError::InvalidHashedUserId
+        );
+        require!(
+            recipient_hashed_user_id != ctx.accounts.vault.hashed_user_id,
+            TipError::InvalidRecipientPda
+        );
+        require!(
+            interval_seconds >= MIN_SUBSCRIPTION_INTERVAL_SECONDS,
+            TipError::InvalidSubscriptionInterval
+        );
+        require!(periods > 0, TipError::InvalidSubscriptionPeriods);
+
+        let now = Clock::get()?.unix_timestamp;
+        let next_due = first_due.max(now);
+
+        let vault = &ctx.accounts.vault;
+        let subscription = &mut ctx.accounts.subscription;
+
+        subscription.sender_vault = vault.key();
+        subscription.authority = vault.authority;
+        subscription.sender_hashed_user_id = vault.hashed_user_id;
+        subscription.recipient_hashed_user_id = recipient_hashed_user_id;
+        subscription.amount = amount;
+        subscription.interval_seconds = interval_seconds;
+        subscription.next_due = next_due;
+        subscription.remaining_periods = periods;
+        subscription.bump = ctx.bumps.subscription;
+
+        emit!(SubscriptionCreated {
+            authority: subscription.authority,
+            subscription: subscription.key(),
+            sender_vault: subscription.sender_vault,
+            sender_hashed_user_id: subscription.sender_hashed_user_id,
+            recipient_hashed_user_id,
+            amount,
+            interval_seconds,
+            next_due,
+            remaining_periods: periods,
+        });
+
+        Ok(())
+    }
+
+    pub fn cancel_subscription(ctx: Context<CancelSubscription>) -> Result<()> {
+        let subscription = &ctx.accounts.subscription;
+
+        emit!(SubscriptionCancelled {
+            authority: subscription.authority,
+            subscription: subscription.key(),
+            sender_vault: subscription.sender_vault,
+            sender_hashed_user_id: subscription.sender_hashed_user_id,
+            recipient_hashed_user_id: subscription.recipient_hashed_user_id,
+            remaining_periods: subscription.remaining_periods,
+        });
+
+        Ok(())
+    }
+
+    pub fn execute_subscription(ctx: Context<ExecuteSubscription>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let config_key = ctx.accounts.config.key();
+        let config_token_mint = ctx.accounts.config.token_mint;
+        let subscription = &mut ctx.accounts.subscription;
+        let sender_vault = &mut ctx.accounts.sender_vault;
+        let recipient_vault = &mut ctx.accounts.recipient_vault;
+        let fee_vault = &mut ctx.accounts.fee_vault;
+        let unclaimed_authority = unclaimed_authority_key(ctx.program_id);
+
+        require!(
+            subscription.remaining_periods > 0,
+            TipError::SubscriptionFinished
+        );
+        require!(now >= subscription.next_due, TipError::SubscriptionNotDue);
+        require_keys_eq!(
+            sender_vault.authority,
+            subscription.authority,
+            TipError::InvalidAuthority
+        );
+        require_keys_eq!(
+            sender_vault.token_mint,
+            config_token_mint,
+            TipError::InvalidTokenMint
+        );
+
+        init_or_verify_recipient_vault(
+            recipient_vault,
+            subscription.recipient_hashed_user_id,
+            config_token_mint,
+            unclaimed_authority,
+        )?;
+        init_or_verify_fee_vault(
+            fee_vault,
+            config_key,
+            config_token_mint,
+            ctx.bumps.fee_vault,
+        )?;
+
+        let amount = subscription.amount;
+        let fee_bps = ctx.accounts.config.fee_bps;
+        let fee_amount = calculate_fee(amount, fee_bps)?;
+        let total_amount = amount
+            .checked_add(fee_amount)
+            .ok_or(TipError::FeeCalculationOverflow)?;
+        let sender_hash = sender_vault.hashed_user_id;
+        let sender_vault_bump = ctx.bumps.sender_vault;
+
+        consume_withdraw_limit(sender_vault, total_amount, now)?;
+
+        transfer_from_vault(
+            ctx.accounts.token_program.to_account_info(),
+            ctx.accounts.sender_vault_token_account.to_account_info(),
+            ctx.accounts.recipient_vault_token_account.to_account_info(),
+            sender_vault.to_account_info(),
+            &sender_hash,
+            sender_vault_bump,
+            amount,
+        )?;
+
+        if fee_amount > 0 {
+            transfer_from_vault(
+                ctx.accounts.token_program.to_account_info(),
+                ctx.accounts.sender_vault_token_account.to_account_info(),
+                ctx.accounts.fee_vault_token_account.to_account_info(),
+                sender_vault.to_account_info(),
+                &sender_hash,
+                sender_vault_bump,
+                fee_amount,
+            )?;
+        }
+
+        let scheduled_next_due = subscription
+            .next_due
+            .checked_add(subscription.interval_seconds)
+            .ok_or(TipError::InvalidTimestamp)?;
+        subscription.next_due = if scheduled_next_due > now {
+            scheduled_next_due
+        } else {
+            now.checked_add(subscription.interval_seconds)
+                .ok_or(TipError::InvalidTimestamp)?
+        };
+        subscription.remaining_periods -= 1;
+
+        emit!(SubscriptionPayment {
+            subscription: subscription.key(),
+            cranker: ctx.accounts.payer.key(),
+            sender_vault: sender_vault.key(),
+            recipient_vault: recipient_vault.key(),
+            sender_hashed_user_id: sender_hash,
+            recipient_hashed_user_id: subscription.recipient_hashed_user_id,
+            amount,
+            fee_vault: fee_vault.key(),
+            fee_amount,
+            fee_bps,
+            total_amount,
+            next_due: subscription.next_due,
+            remaining_periods: subscription.remaining_periods,
+        });
+
+        Ok(())
+    }
+
+    pub fn create_pool(
+        ctx: Context<CreatePool>,
+        pool_id: [u8; 32],
+        recipient_hashed_user_id: [u8; 32],
+        target_amount: u64,
+        deadline: i64,
+    ) -> Result<()> {
+        require!(
+            recipient_hashed_user_id.iter().any(|byte| *byte != 0),
+            TipError::InvalidHashedUserId
+        );
+        require!(target_amount > 0, TipError::InvalidPoolTarget);
+
+        let now = Clock::get()?.unix_timestamp;
+        require!(deadline > now, TipError::InvalidPoolDeadline);
+
+        let pool = &mut ctx.accounts.pool;
+        pool.pool_id = pool_id;
+        pool.creator = ctx.accounts.creator.key();
+        pool.recipient_hashed_user_id = recipient_hashed_user_id;
+        pool.token_mint = ctx.accounts.config.token_mint;
+        pool.target_amount = target_amount;
+        pool.deadline = deadline;
+        pool.total_contributed = 0;
+        pool.total_fees = 0;
+        pool.contribution_count = 0;
+        pool.claimed = false;
+        pool.bump = ctx.bumps.pool;
+
+        emit!(PoolCreated {
+            creator: pool.creator,
+            pool: pool.key(),
+            pool_id,
+            recipient_hashed_user_id,
+            target_amount,
+            deadline,
+        });
+
+        Ok(())
+    }
+
+    pub fn contribute_to_pool(
+        ctx: Context<ContributeToPool>,
+        amount: u64,
+        sender_nonce: u64,
+        valid_until: i64,
+    ) -> Result<()> {
+        require!(amount > 0, TipError::InvalidTipAmount);
+
+        let now = Clock::get()?.unix_timestamp;
+        require!(valid_until == 0 || now <= valid_until, TipError::TipExpired);
+
+        let config_token_mint = ctx.accounts.config.token_mint;
+        let pool = &mut ctx.accounts.pool;
+        let sender_vault = &mut ctx.accounts.sender_vault;
+        let sender_allowance = &mut ctx.accounts.sender_allowance;
+
+        require!(!pool.claimed, TipError::PoolAlreadyClaimed);
+        require!(now < pool.deadline, TipError::PoolExpired);
+        require_keys_eq!(
+            sender_vault.token_mint,
+            config_token_mint,
+            TipError::InvalidTokenMint
+        );
+        require_keys_eq!(
+            sender_vault.authority,
+            sender_allowance.authority,
+            TipError::InvalidAuthority
+        );
+
+        consume_tip_rate_limit(sender_vault, &ctx.accounts.config, 1, now)?;
+
+        if sender_vault.require_tip_signature {
+            let intent = PoolContributionIntent {
+                sender_vault: sender_vault.key(),
+                spender: ctx.accounts.relayer.key(),
+                pool: pool.key(),
+                amount,
+                sender_nonce,
+                valid_until,
+            };
+            verify_tip_intent(
+                ctx.accounts.instructions_sysvar.as_ref(),
+                &sender_vault.authority,
+                POOL_CONTRIBUTION_INTENT_DOMAIN,
+                valid_until,
+                &intent,
+            )?;
+        }
+
+        check_min_tip_amount(&ctx.accounts.config, amount, false)?;
+        check_allowance_recipient(
+            sender_allowance,
+            ctx.accounts.recipient_rules.as_deref(),
+            &pool.recipient_hashed_user_id,
+            amount,
+        )?;
+
+        let sender_hash = sender_vault.hashed_user_id;
+        let sender_vault_bump = ctx.bumps.sender_vault;
+        let fee_bps = ctx.accounts.config.fee_bps;
+        let fee_amount = calculate_fee(amount, fee_bps)?;
+        let total_amount = amount
+            .checked_add(fee_amount)
+            .ok_or(TipError::FeeCalculationOverflow)?;
+
+        spend_allowance(
+            sender_allowance,
+            sender_vault,
+            sender_vault_bump,
+            sender_nonce,
+            total_amount,
+            now,
+        )?;
+
+        transfer_from_vault(
+            ctx.accounts.token_program.to_account_info(),
+            ctx.accounts.sender_vault_token_account.to_account_info(),
+            ctx.accounts.pool_token_account.to_account_info(),
+            sender_vault.to_account_info(),
+            &sender_hash,
+            sender_vault_bump,
+            total_amount,
+        )?;
+
+        pool.total_contributed = pool
+            .total_contributed
+            .checked_add(amount)
+            .ok_or(TipError::FeeCalculationOverflow)?;
+        pool.total_fees = pool
+            .total_fees
+            .checked_add(fee_amount)
+            .ok_or(TipError::FeeCalculationOverflow)?;
+
+        let contribution = &mut ctx.accounts.pool_contribution;
+        if contribution.pool == Pubkey::default() {
+            contribution.pool = pool.key();
+            contribution.sender_vault = sender_vault.key();
+            contribution.payer = ctx.accounts.relayer.key();
+            contribution.bump = ctx.bumps.pool_contribution;
+            pool.contribution_count = pool
+                .contribution_count
+                .checked_add(1)
+                .ok_or(TipError::FeeCalculationOverflow)?;
+        }
+        contribution.amount = contribution
+            .amount
+            .checked_add(amount)
+            .ok_or(TipError::FeeCalculationOverflow)?;
+        contribution.fee_amount = contribution
+            .fee_amount
+            .checked_add(fee_amount)
+            .ok_or(TipError::FeeCalculationOverflow)?;
+
+        emit!(PoolContributed {
+            relayer: ctx.accounts.relayer.key(),
+            pool: pool.key(),
+            pool_id: pool.pool_id,
+            sender_vault: sender_vault.key(),
+            sender_hashed_user_id: sender_hash,
+            amount,
+            sender_nonce,
+            allowance_remaining: sender_allowance.remaining,
+            fee_amount,
+            fee_bps,
+            total_amount,
+            contribution_amount: contribution.amount,
+            pool_total_contributed: pool.total_contributed,
+        });
+
+        Ok(())
+    }
+
+    pub fn claim_pool(ctx: Context<ClaimPool>) -> Result<()> {
+        let config_key = ctx.accounts.config.key();
+        let config_token_mint = ctx.accounts.config.token_mint;
+        let unclaimed_authority = unclaimed_authority_key(ctx.program_id);
+        let pool = &mut ctx.accounts.pool;
+
+        require!(!pool.claimed, TipError::PoolAlreadyClaimed);
+        require!(
+            pool.total_contributed >= pool.target_amount,
+            TipError::PoolNotFunded
+        );
+
+        init_or_verify_recipient_vault(
+            &mut ctx.accounts.recipient_vault,
+            pool.recipient_hashed_user_id,
+            config_token_mint,
+            unclaimed_authority,
+        )?;
+        let authority = ctx.accounts.authority.key();
+        require!(
+            authority == ctx.accounts.recipient_vault.authority
+                || authority == ctx.accounts.config.claim_authority,
+            TipError::InvalidAuthority
+        );
+        init_or_verify_fee_vault(
+            &mut ctx.accounts.fee_vault,
+            config_key,
+            config_token_mint,
+            ctx.bumps.fee_vault,
+        )?;
+
+        let balance = ctx.accounts.pool_token_account.amount;
+        let fee_amount = pool.total_fees.min(balance);
+        let amount = balance - fee_amount;
+
+        transfer_from_pool(
+            ctx.accounts.token_program.to_account_info(),
+            ctx.accounts.pool_token_account.to_account_info(),
+            ctx.accounts.recipient_vault_token_account.to_account_info(),
+            pool,
+            amount,
+        )?;
+
+        if fee_amount > 0 {
+            transfer_from_pool(
+                ctx.accounts.token_program.to_account_info(),
+                ctx.accounts.pool_token_account.to_account_info(),
+                ctx.accounts.fee_vault_token_account.to_account_info(),
+                pool,
+                fee_amount,
+            )?;
+        }
+
+        close_pool_token_account(
+            ctx.accounts.token_program.to_account_info(),
+            ctx.accounts.pool_token_account.to_account_info(),
+            ctx.accounts.creator.to_account_info(),
+            pool,
+        )?;
+
+        pool.claimed = true;
+
+        emit!(PoolClaimed {
+            pool: pool.key(),
+            pool_id: pool.pool_id,
+            recipient_vault: ctx.accounts.recipient_vault.key(),
+            recipient_hashed_user_id: pool.recipient_hashed_user_id,
+            amount,
+            fee_amount,
+        });
+
+        Ok(())
+    }
+
+    pub fn refund_pool_contribution(ctx: Context<RefundPoolContribution>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let pool = &mut ctx.accounts.pool;
+        let contribution = &ctx.accounts.pool_contribution;
+
+        require!(now >= pool.deadline, TipError::PoolStillOpen);
+        require!(
+            pool.total_contributed < pool.target_amount,
+            TipError::PoolAlreadyFunded
+        );
+
+        let total_amount = contribution
+            .amount
+            .checked_add(contribution.fee_amount)
+            .ok_or(TipError::FeeCalculationOverflow)?;
+
+        transfer_from_pool(
+            ctx.accounts.token_program.to_account_info(),
+            ctx.accounts.pool_token_account.to_account_info(),
+            ctx.accounts.sender_vault_token_account.to_account_info(),
+            pool,
+            total_amount,
+        )?;
+
+        pool.contribution_count = pool.contribution_count.saturating_sub(1);
+
+        emit!(PoolRefunded {
+            pool: pool.key(),
+            pool_id: pool.pool_id,
+            sender_vault: contribution.sender_vault,
+            amount: contribution.amount,
+            fee_amount: contribution.fee_amount,
+        });
+
+        Ok(())
+    }
+
+    pub fn close_pool_contribution(ctx: Context<ClosePoolContribution>) -> Result<()> {
+        let pool = &mut ctx.accounts.pool;
+        require!(pool.claimed, TipError::PoolNotClaimed);
+
+        pool.contribution_count = pool.contribution_count.saturating_sub(1);
+
+        Ok(())
+    }
+
+    pub fn close_pool(ctx: Context<ClosePool>) -> Result<()> {
+        let pool = &ctx.accounts.pool;
+        require!(pool.contribution_count == 0, TipError::PoolHasContributions);
+
+        if !pool.claimed {
+            let now = Clock::get()?.unix_timestamp;
+            require!(now >= pool.deadline, TipError::PoolStillOpen);
+
+            let pool_token_account = ctx
+                .accounts
+                .pool_token_account
+                .as_ref()
+                .ok_or(TipError::MissingPoolTokenAccount)?;
+            if pool_token_account.amount > 0 {
+                let creator_token_account = ctx
+                    .accounts
+                    .creator_token_account
+                    .as_ref()
+                    .ok_or(TipError::MissingCreatorTokenAccount)?;
+                transfer_from_pool(
+                    ctx.accounts.token_program.to_account_info(),
+                    pool_token_account.to_account_info(),
+                    creator_token_account.to_account_info(),
+                    pool,
+                    pool_token_account.amount,
+                )?;
+            }
+            close_pool_token_account(
+                ctx.accounts.token_program.to_account_info(),
+                pool_token_account.to_account_info(),
+                ctx.accounts.creator.to_account_info(),
+                pool,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    pub fn create_bounty(
+        ctx: Context<CreateBounty>,
+        bounty_id: [u8; 32],
+        amount: u64,
+    ) -> Result<()> {
+        require!(amount > 0, TipError::InvalidBountyAmount);
+
+        let now = Clock::get()?.unix_timestamp;
+        let creator_vault = &mut ctx.accounts.creator_vault;
+        let creator_hash = creator_vault.hashed_user_id;
+
+        require_keys_eq!(
+            creator_vault.token_mint,
+            ctx.accounts.config.token_mint,
+            TipError::InvalidTokenMint
+        );
+        require!(
+            ctx.accounts.creator_vault_token_account.amount >= amount,
+            TipError::InsufficientVaultBalance
+        );
+
+        consume_withdraw_limit(creator_vault, amount, now)?;
+
+        transfer_from_vault(
+            ctx.accounts.token_program.to_account_info(),
+            ctx.accounts.creator_vault_token_account.to_account_info(),
+            ctx.accounts.bounty_token_account.to_account_info(),
+            creator_vault.to_account_info(),
+            &creator_hash,
+            ctx.bumps.creator_vault,
+            amount,
+        )?;
+
+        let bounty = &mut ctx.accounts.bounty;
+        bounty.bounty_id = bounty_id;
+        bounty.creator = ctx.accounts.authority.key();
+        bounty.creator_vault = creator_vault.key();
+        bounty.creator_hashed_user_id = creator_hash;
+        bounty.amount = amount;
+        bounty.created_at = now;
+        bounty.bump = ctx.bumps.bounty;
+
+        emit!(BountyCreated {
+            creator: bounty.creator,
+            bounty: bounty.key(),
+            bounty_id,
+            creator_vault: bounty.creator_vault,
+            creator_hashed_user_id: creator_hash,
+            amount,
+        });
+
+        Ok(())
+    }
+
+    pub fn cancel_bounty(ctx: Context<CancelBounty>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let bounty = &ctx.accounts.bounty;
+        let amount = ctx.accounts.bounty_token_account.amount;
+
+        refund_withdraw_limit(
+            &mut ctx.accounts.creator_vault,
+            bounty.amount,
+            bounty.created_at,
+            now,
+        );
+
+        release_bounty_escrow(
+            ctx.accounts.token_program.to_account_info(),
+            ctx.accounts.bounty_token_account.to_account_info(),
+            ctx.accounts.creator_vault_token_account.to_account_info(),
+            bounty,
+            ctx.accounts.creator.to_account_info(),
+            amount,
+        )?;
+
+        emit!(BountyCancelled {
+            creator: bounty.creator,
+            bounty: bounty.key(),
+            bounty_id: bounty.bounty_id,
+            creator_vault: bounty.creator_vault,
+            amount,
+        });
+
+        Ok(())
+    }
+
+    pub fn award_bounty(
+        ctx: Context<AwardBounty>,
+        recipient_hashed_user_id: [u8; 32],
+    ) -> Result<()> {
+        require!(
+            recipient_hashed_user_id.iter().any(|byte| *byte != 0),
+            TipError::InvalidHashedUserId
+        );
+
+        let bounty = &ctx.accounts.bounty;
+        let awarded_by = ctx.accounts.authority.key();
+        require!(
+            awarded_by == bounty.creator || awarded_by == ctx.accounts.config.claim_authority,
+            TipError::InvalidAuthority
+        );
+
+        let unclaimed_authority = unclaimed_authority_key(ctx.program_id);
+        init_or_verify_recipient_vault(
+            &mut ctx.accounts.recipient_vault,
+            recipient_hashed_user_id,
+            ctx.accounts.config.token_mint,
+            unclaimed_authority,
+        )?;
+
+        let amount = ctx.accounts.bounty_token_account.amount;
+        release_bounty_escrow(
+            ctx.accounts.token_program.to_account_info(),
+            ctx.accounts.bounty_token_account.to_account_info(),
+            ctx.accounts.recipient_vault_token_account.to_account_info(),
+            bounty,
+            ctx.accounts.creator.to_account_info(),
+            amount,
+        )?;
+
+        emit!(BountyAwarded {
+            creator: bounty.creator,
+            awarded_by,
+            bounty: bounty.key(),
+            bounty_id: bounty.bounty_id,
+            creator_vault: bounty.creator_vault,
+            recipient_vault: ctx.accounts.recipient_vault.key(),
+            recipient_hashed_user_id,
+            amount,
+        });
+
+        Ok(())
+    }
+
+    pub fn register_merchant(ctx: Context<RegisterMerchant>) -> Result<()> {
+        let merchant = &mut ctx.accounts.merchant;
+        merchant.authority = ctx.accounts.merchant_authority.key();
+        merchant.payout_token_account = ctx.accounts.payout_token_account.key();
+        merchant.bump = ctx.bumps.merchant;
+
+        emit!(MerchantUpdated {
+            merchant: merchant.key(),
+            authority: merchant.authority,
+            payout_token_account: merchant.payout_token_account,
+            active: true,
+        });
+
+        Ok(())
+    }
+
+    pub fn update_merchant(ctx: Context<UpdateMerchant>) -> Result<()> {
+        let merchant = &mut ctx.accounts.merchant;
+        merchant.payout_token_account = ctx.accounts.payout_token_account.key();
+
+        emit!(MerchantUpdated {
+            merchant: merchant.key(),
+            authority: merchant.authority,
+            payout_token_account: merchant.payout_token_account,
+            active: true,
+        });
+
+        Ok(())
+    }
+
+    pub fn remove_merchant(ctx: Context<RemoveMerchant>) -> Result<()> {
+        let merchant = &ctx.accounts.merchant;
+
+        emit!(MerchantUpdated {
+            merchant: merchant.key(),
+            authority: merchant.authority,
+            payout_token_account: merchant.payout_token_account,
+            active: false,
+        });
+
+        Ok(())
+    }
+
+    pub fn create_invoice(ctx: Context<CreateInvoice>, args: CreateInvoiceArgs) -> Result<()> {
+        let recipient_vault = &ctx.accounts.recipient_vault;
+        require!(recipient_vault.claimed, TipError::InvalidAuthority);
+        require!(
+            args.payer_hashed_user_id != recipient_vault.hashed_user_id,
+            TipError::InvalidInvoicePayer
+        );
+
+        init_invoice(
+            &mut ctx.accounts.invoice,
+            &args,
+            ctx.accounts.authority.key(),
+            recipient_vault.hashed_user_id,
+            Pubkey::default(),
+            ctx.bumps.invoice,
+        )
+    }
+
+    pub fn create_merchant_invoice(
+        ctx: Context<CreateMerchantInvoice>,
+        args: CreateInvoiceArgs,
+    ) -> Result<()> {
+        init_invoice(
+            &mut ctx.accounts.invoice,
+            &args,
+            ctx.accounts.authority.key(),
+            [0u8; 32],
+            ctx.accounts.merchant.key(),
+            ctx.bumps.invoice,
+        )
+    }
+
+    pub fn cancel_invoice(ctx: Context<CancelInvoice>) -> Result<()> {
+        let invoice = &ctx.accounts.invoice;
+        require!(!invoice.paid, TipError::InvoiceAlreadyPaid);
+
+        emit!(InvoiceCancelled {
+            invoice: invoice.key(),
+            invoice_id: invoice.invoice_id,
+            creator: invoice.creator,
+            reference: invoice.reference,
+        });
+
+        Ok(())
+    }
+
+    pub fn close_invoice(ctx: Context<CloseInvoice>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let invoice = &ctx.accounts.invoice;
+
+        let closable = if invoice.paid {
+            let reclaimable_at = invoice
+                .paid_at
+                .checked_add(INVOICE_RETENTION_SECONDS)
+                .ok_or(TipError::InvalidTimestamp)?;
+            now >= reclaimable_at
+        } else {
+            let expired = invoice.expires_at != 0 && now > invoice.expires_at;
+            let merchant_removed = invoice.merchant != Pubkey::default()
+                && ctx
+                    .accounts
+                    .merchant
+                    .as_ref()
+                    .is_some_and(|merchant| merchant.data_is_empty());
+            expired || merchant_removed
+        };
+        require!(closable, TipError::InvoiceNotClosable);
+
+        Ok(())
+    }
+
+    pub fn pay_invoice(
+        ctx: Context<PayInvoice>,
+        sender_nonce: u64,
+        valid_until: i64,
+    ) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        require!(valid_until == 0 || now <= valid_until, TipError::TipExpired);
+
+        let config_key = ctx.accounts.config.key();
+        let config_token_mint = ctx.accounts.config.token_mint;
+        let invoice = &mut ctx.accounts.invoice;
+        let sender_vault = &mut ctx.accounts.sender_vault;
+        let sender_allowance = &mut ctx.accounts.sender_allowance;
+        let destination_token_account = &ctx.accounts.destination_token_account;
+
+        require!(!invoice.paid, TipError::InvoiceAlreadyPaid);
+        require!(
+            invoice.expires_at == 0 || now <= invoice.expires_at,
+            TipError::InvoiceExpired
+        );
+        require!(
+            sender_vault.hashed_user_id == invoice.payer_hashed_user_id,
+            TipError::InvalidInvoicePayer
+        );
+        require_keys_eq!(
+            sender_vault.token_mint,
+            config_token_mint,
+            TipError::InvalidTokenMint
+        );
+        require_keys_eq!(
+            sender_vault.authority,
+            sender_allowance.authority,
+            TipError::InvalidAuthority
+        );
+
+        let recipient_hash = if invoice.merchant == Pubkey::default() {
+            let (recipient_vault, _) = Pubkey::find_program_address(
+                &[b"vault", invoice.recipient_hashed_user_id.as_ref()],
+                ctx.program_id,
+            );
+            require_keys_eq!(
+                destination_token_account.owner,
+                recipient_vault,
+                TipError::InvalidDestination
+            );
+            Some(invoice.recipient_hashed_user_id)
+        } else {
+            let merchant = ctx
+                .accounts
+                .merchant
+                .as_ref()
+                .ok_or(TipError::InvalidMerchant)?;
+            require_keys_eq!(merchant.key(), invoice.merchant, TipError::InvalidMerchant);
+            require_keys_eq!(
+                destination_token_account.key(),
+                merchant.payout_token_account,
+                TipError::InvalidDestination
+            );
+            None
+        };
+
+        consume_tip_rate_limit(sender_vault, &ctx.accounts.config, 1, now)?;
+
+        if sender_vault.require_tip_signature {
+            let intent = InvoicePaymentIntent {
+                sender_vault: sender_vault.key(),
+                spender: ctx.accounts.relayer.key(),
+                invoice: invoice.key(),
+                amount: invoice.amount,
+                sender_nonce,
+                valid_until,
+            };
+            verify_tip_intent(
+                ctx.accounts.instructions_sysvar.as_ref(),
+                &sender_vault.authority,
+                INVOICE_PAYMENT_INTENT_DOMAIN,
+                valid_until,
+                &intent,
+            )?;
+        }
+
+        let amount = invoice.amount;
+        match recipient_hash {
+            Some(recipient_hash) => check_allowance_recipient(
+                sender_allowance,
+                ctx.accounts.recipient_rules.as_deref(),
+                &recipient_hash,
+                amount,
+            )?,
+            None => check_wallet_recipient(sender_allowance, amount)?,
+        }
+
+        init_or_verify_fee_vault(
+            &mut ctx.accounts.fee_vault,
+            config_key,
+            config_token_mint,
+            ctx.bumps.fee_vault,
+        )?;
+
+        let sender_hash = sender_vault.hashed_user_id;
+        let sender_vault_bump = ctx.bumps.sender_vault;
+        let fee_bps = ctx.accounts.config.fee_bps;
+        let fee_amount = calculate_fee(amount, fee_bps)?;
+        let total_amount = amount
+            .checked_add(fee_amount)
+            .ok_or(TipError::FeeCalculationOverflow)?;
+
+        spend_allowance(
+            sender_allowance,
+            sender_vault,
+            sender_vault_bump,
+            sender_nonce,
+            total_amount,
+            now,
+        )?;
+
+        invoice.paid = true;
+        invoice.paid_at = now;
+
+        transfer_from_vault(
+            ctx.accounts.token_program.to_account_info(),
+            ctx.accounts.sender_vault_token_account.to_account_info(),
+            destination_token_account.to_account_info(),
+            sender_vault.to_account_info(),
+            &sender_hash,
+            sender_vault_bump,
+            amount,
+        )?;
+
+        if fee_amount > 0 {
+            transfer_from_vault(
+                ctx.accounts.token_program.to_account_info(),
+                ctx.accounts.sender_vault_token_account.to_account_info(),
+                ctx.accounts.fee_vault_token_account.to_account_info(),
+                sender_vault.to_account_info(),
+                &sender_hash,
+                sender_vault_bump,
+                fee_amount,
+            )?;
+        }
+
+        emit!(InvoicePaid {
+            relayer: ctx.accounts.relayer.key(),
+            invoice: invoice.key(),
+            invoice_id: invoice.invoice_id,
+            reference: invoice.reference,
+            merchant: invoice.merchant,
+            recipient_hashed_user_id: invoice.recipient_hashed_user_id,
+            sender_vault: sender_vault.key(),
+            sender_hashed_user_id: sender_hash,
+            destination_token_account: destination_token_account.key(),
+            amount,
+            sender_nonce,
+            allowance_remaining: sender_allowance.remaining,
+            fee_amount,
+            fee_bps,
+            total_amount,
+        });
+
+        Ok(())
+    }
+
+    pub fn create_group_vault(
+        ctx: Context<CreateGroupVault>,
+        group_id: [u8; 32],
+        members: Vec<Pubkey>,
+        threshold: u8,
+    ) -> Result<()> {
+        require!(
+            group_id.iter().any(|byte| *byte != 0),
+            TipError::InvalidHashedUserId
+        );
+        require_keys_eq!(
+            ctx.accounts.claim_authority.key(),
+            ctx.accounts.config.claim_authority,
+            TipError::InvalidClaimAuthority
+        );
+        require!(
+            members.contains(&ctx.accounts.creator.key()),
+            TipError::NotGroupMember
+        );
+        validate_group_members(&members, threshold)?;
+
+        let group_vault = &mut ctx.accounts.group_vault;
+        let vault = &mut ctx.accounts.vault;
+
+        init_or_verify_recipient_vault(
+            vault,
+            group_vault.key().to_bytes(),
+            ctx.accounts.config.token_mint,
+            unclaimed_authority_key(ctx.program_id),
+        )?;
+        require!(!vault.claimed, TipError::VaultAlreadyClaimed);
+        vault.authority = group_vault.key();
+        vault.claimed = true;
+
+        group_vault.group_id = group_id;
+        group_vault.vault = vault.key();
+        group_vault.members = members;
+        group_vault.threshold = threshold;
+        group_vault.version = 0;
+        group_vault.proposal_count = 0;
+        group_vault.bump = ctx.bumps.group_vault;
+
+        emit!(GroupVaultCreated {
+            group_vault: group_vault.key(),
+            vault: group_vault.vault,
+            group_id,
+            members: group_vault.members.clone(),
+            threshold,
+        });
+
+        Ok(())
+    }
+
+    pub fn propose_group_action(
+        ctx: Context<ProposeGroupAction>,
+        action: GroupProposalAction,
+    ) -> Result<()> {
+        let group_vault = &mut ctx.accounts.group_vault;
+        let proposer = ctx.accounts.proposer.key();
+        require!(
+            group_vault.members.contains(&proposer),
+            TipError::NotGroupMember
+        );
+        if let GroupProposalAction::Withdraw { amount, .. } = action {
+            require!(amount > 0, TipError::InvalidWithdrawAmount);
+        }
+
+        let proposal_id = group_vault.proposal_count;
+        group_vault.proposal_count = proposal_id
+            .checked_add(1)
+            .ok_or(TipError::InvalidGroupProposal)?;
+
+        let proposal = &mut ctx.accounts.proposal;
+        proposal.group_vault = group_vault.key();
+        proposal.proposal_id = proposal_id;
+        proposal.proposer = proposer;
+        proposal.action = action;
+        proposal.version = group_vault.version;
+        proposal.approvals = 0;
+        proposal.created_at = Clock::get()?.unix_timestamp;
+        proposal.bump = ctx.bumps.proposal;
+
+        emit!(GroupProposalCreated {
+            group_vault: proposal.group_vault,
+            proposal: proposal.key(),
+            proposal_id,
+            proposer,
+            action: proposal.action.clone(),
+            version: proposal.version,
+        });
+
+        Ok(())
+    }
+
+    pub fn approve_group_proposal(ctx: Context<ApproveGroupProposal>) -> Result<()> {
+        let group_vault = &ctx.accounts.group_vault;
+        let proposal = &mut ctx.accounts.proposal;
+        let member = ctx.accounts.member.key();
+
+        require!(
+            group_vault.members.contains(&member),
+            TipError::NotGroupMember
+        );
+        require!(
+            proposal.version == group_vault.version,
+            TipError::StaleGroupProposal
+        );
+
+        proposal.approvals = proposal
+            .approvals
+            .checked_add(1)
+            .ok_or(TipError::InvalidGroupProposal)?;
+
+        let approval = &mut ctx.accounts.approval;
+        approval.proposal = proposal.key();
+        approval.member = member;
+        approval.bump = ctx.bumps.approval;
+
+        emit!(GroupProposalApproved {
+            group_vault: group_vault.key(),
+            proposal: proposal.key(),
+            proposal_id: proposal.proposal_id,
+            member,
+            approvals: proposal.approvals,
+            threshold: group_vault.threshold,
+        });
+
+        Ok(())
+    }
+
+    pub fn execute_group_proposal(ctx: Context<ExecuteGroupProposal>) -> Result<()> {
+        let group_vault = &mut ctx.accounts.group_vault;
+        let proposal = &ctx.accounts.proposal;
+        let executed_by = ctx.accounts.member.key();
+
+        require!(
+            group_vault.members.contains(&executed_by),
+            TipError::NotGroupMember
+        );
+        require!(
+            proposal.version == group_vault.version,
+            TipError::StaleGroupProposal
+        );
+        require!(
+            proposal.approvals >= group_vault.threshold,
+            TipError::GroupProposalNotApproved
+        );
+
+        match proposal.action.clone() {
+            GroupProposalAction::Withdraw {
+                destination,
+                amount,
+            } => {
+                let vault_token_account = ctx
+                    .accounts
+                    .vault_token_account
+                    .as_ref()
+                    .ok_or(TipError::InvalidGroupProposal)?;
+                let destination_token_account = ctx
+                    .accounts
+                    .destination_token_account
+                    .as_ref()
+                    .ok_or(TipError::InvalidGroupProposal)?;
+                require_keys_eq!(
+                    destination_token_account.key(),
+                    destination,
+                    TipError::InvalidDestination
+                );
+
+                withdraw_from_vault(
+                    &ctx.accounts.config,
+                    &mut ctx.accounts.vault,
+                    vault_token_account,
+                    destination_token_account,
+                    ctx.accounts.token_program.to_account_info(),
+                    ctx.bumps.vault,
+                    amount,
+                )?;
+            }
+            GroupProposalAction::SetWithdrawLimit { daily_limit } => {
+                let now = Clock::get()?.unix_timestamp;
+                update_withdraw_limit(&mut ctx.accounts.vault, daily_limit, now)?;
+            }
+            GroupProposalAction::AddMember { member, threshold } => {
+                require!(
+                    !group_vault.members.contains(&member),
+                    TipError::InvalidGroupMembers
+                );
+                let mut members = group_vault.members.clone();
+                members.push(member);
+                update_group_members(group_vault, members, threshold)?;
+            }
+            GroupProposalAction::RemoveMember { member, threshold } => {
+                require!(
+                    group_vault.members.contains(&member),
+                    TipError::NotGroupMember
+                );
+                let mut members = group_vault.members.clone();
+                members.retain(|existing| *existing != member);
+                update_group_members(group_vault, members, threshold)?;
+            }
+        }
+
+        emit!(GroupProposalExecuted {
+            group_vault: group_vault.key(),
+            proposal: proposal.key(),
+            proposal_id: proposal.proposal_id,
+            executed_by,
+            action: proposal.action.clone(),
+        });
+
+        Ok(())
+    }
+
+    pub fn cancel_group_proposal(ctx: Context<CancelGroupProposal>) -> Result<()> {
+        let proposal = &ctx.accounts.proposal;
+
+        emit!(GroupProposalCancelled {
+            group_vault: proposal.group_vault,
+            proposal: proposal.key(),
+            proposal_id: proposal.proposal_id,
+            proposer: proposal.proposer,
+        });
+
+        Ok(())
+    }
+
+    pub fn close_group_approval(ctx: Context<CloseGroupApproval>) -> Result<()> {
+        require!(
+            ctx.accounts.proposal.data_is_empty(),
+            TipError::InvalidGroupProposal
+        );
+
+        Ok(())
+    }
+
+    pub fn close_tip_receipt(ctx: Context<CloseTipReceipt>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let reclaimable_at = ctx
+            .accounts
+            .tip_receipt
+            .created_at
+            .checked_add(TIP_RECEIPT_RETENTION_SECONDS)
+            .ok_or(TipError::InvalidTimestamp)?;
+
+        require!(now >= reclaimable_at, TipError::TipReceiptNotExpired);
+
+        Ok(())
+    }
+
+    pub fn withdraw(ctx: Context<Withdraw>, amount: u64) -> Result<()> {
+        require!(amount > 0, TipError::InvalidWithdrawAmount);
+
+        let accounts = ctx.accounts;
+        withdraw_from_vault(
+            &accounts.config,
+            &mut accounts.vault,
+            &accounts.vault_token_account,
+            &accounts.destination_token_account,
+            accounts.token_program.to_account_info(),
+            ctx.bumps.vault,
+            amount,
+        )
+    }
+
+    pub fn withdraw_all(ctx: Context<Withdraw>) -> Result<()> {
+        let amount = ctx.accounts.vault_token_account.amount;
+        require!(amount > 0, TipError::InsufficientVaultBalance);
+
+        let accounts = ctx.accounts;
+        withdraw_from_vault(
+            &accounts.config,
+            &mut accounts.vault,
+            &accounts.vault_token_account,
+            &accounts.destination_token_account,
+            accounts.token_program.to_account_info(),
+            ctx.bumps.vault,
+            amount,
+        )
+    }
+
+    pub fn withdraw_to_wallet(ctx: Context<WithdrawToWallet>, amount: u64) -> Result<()> {
+        require!(amount > 0, TipError::InvalidWithdrawAmount);
+
+        let accounts = ctx.accounts;
+        withdraw_from_vault(
+            &accounts.config,
+            &mut accounts.vault,
+            &accounts.vault_token_account,
+            &accounts.destination_token_account,
+            accounts.token_program.to_account_info(),
+            ctx.bumps.vault,
+            amount,
+        )
+    }
+
+    pub fn withdraw_all_to_wallet(ctx: Context<WithdrawToWallet>) -> Result<()> {
+        let amount = ctx.accounts.vault_token_account.amount;
+        require!(amount > 0, TipError::InsufficientVaultBalance);
+
+        let accounts = ctx.accounts;
+        withdraw_from_vault(
+            &accounts.config,
+            &mut accounts.vault,
+            &accounts.vault_token_account,
+            &accounts.destination_token_account,
+            accounts.token_program.to_account_info(),
+            ctx.bumps.vault,
+            amount,
+        )
+    }
+
+    pub fn relayed_withdraw(ctx: Context<RelayedWithdraw>, intent: WithdrawIntent) -> Result<()> {
+        require!(intent.amount > 0, TipError::InvalidWithdrawAmount);
+        require_keys_eq!(
+            intent.vault,
+            ctx.accounts.vault.key(),
+            TipError::IntentMismatch
+        );
+        require_keys_eq!(
+            intent.destination,
+            ctx.accounts.destination.key(),
+            TipError::IntentMismatch
+        );
+
+        let now = Clock::get()?.unix_timestamp;
+        require!(now <= intent.expires_at, TipError::IntentExpired);
+        require!(
+            intent.nonce > ctx.accounts.vault.withdraw_nonce,
+            TipError::InvalidIntentNonce
+        );
+
+        let mut message = WITHDRAW_INTENT_DOMAIN.to_vec();
+        intent
+            .serialize(&mut message)
+            .map_err(|_| TipError::IntentMismatch)?;
+        verify_ed25519_instruction(
+            &ctx.accounts.instructions_sysvar,
+            &ctx.accounts.vault.authority,
+            &message,
+        )?;
+
+        let total_amount = intent
+            .amount
+            .checked_add(intent.relayer_fee)
+            .ok_or(TipError::FeeCalculationOverflow)?;
+        require!(
+            ctx.accounts.vault_token_account.amount >= total_amount,
+            TipError::InsufficientVaultBalance
+        );
+
+        let vault = &mut ctx.accounts.vault;
+        consume_withdraw_limit(vault, total_amount, now)?;
+        vault.withdraw_nonce = intent.nonce;
+
+        let vault_bump = ctx.bumps.vault;
+        transfer_from_vault(
+            ctx.accounts.token_program.to_account_info(),
+            ctx.accounts.vault_token_account.to_account_info(),
+            ctx.accounts.destination_token_account.to_account_info(),
+            vault.to_account_info(),
+            &vault.hashed_user_id,
+            vault_bump,
+            intent.amount,
+        )?;
+
+        if intent.relayer_fee > 0 {
+            transfer_from_vault(
+                ctx.accounts.token_program.to_account_info(),
+                ctx.accounts.vault_token_account.to_account_info(),
+                ctx.accounts.relayer_token_account.to_account_info(),
+                vault.to_account_info(),
+                &vault.hashed_user_id,
+                vault_bump,
+                intent.relayer_fee,
+            )?;
+        }
+
+        emit!(RelayedWithdrawEvent {
+            relayer: ctx.accounts.relayer.key(),
+            authority: vault.authority,
+            vault: vault.key(),
+            vault_bump,
+            hashed_user_id: vault.hashed_user_id,
+            destination: intent.destination,
+            destination_token_account: ctx.accounts.destination_token_account.key(),
+            amount: intent.amount,
+            relayer_fee: intent.relayer_fee,
+            nonce: intent.nonce,
+        });
+
+        Ok(())
+    }
+
+    pub fn migrate_vault(ctx: Context<MigrateVault>, _hashed_user_id: [u8; 32]) -> Result<()> {
+        realloc_legacy_account(
+            &ctx.accounts.vault.to_account_info(),
+            ctx.accounts.payer.to_account_info(),
+            ctx.accounts.system_program.to_account_info(),
+            Vault::DISCRIMINATOR,
+            Vault::SPACE,
+        )
+    }
+
+    pub fn migrate_config(ctx: Context<MigrateConfig>) -> Result<()> {
+        realloc_legacy_account(
+            &ctx.accounts.config.to_account_info(),
+            ctx.accounts.payer.to_account_info(),
+            ctx.accounts.system_program.to_account_info(),
+            Config::DISCRIMINATOR,
+            Config::SPACE,
+        )
+    }
+
+    pub fn set_withdraw_limit(ctx: Context<SetWithdrawLimit>, daily_limit: u64) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        update_withdraw_limit(&mut ctx.accounts.vault, daily_limit, now)
+    }
+
+    pub fn set_tip_signature_required(
+        ctx: Context<SetTipSignatureRequired>,
+        required: bool,
+    ) -> Result<()> {
+        let vault = &mut ctx.accounts.vault;
+        vault.require_tip_signature = required;
+
+        emit!(TipSignatureRequirementUpdated {
+            authority: vault.authority,
+            vault: vault.key(),
+            hashed_user_id: vault.hashed_user_id,
+            require_tip_signature: required,
+        });
+
+        Ok(())
+    }
+
+    pub fn queue_withdrawal(ctx: Context<QueueWithdrawal>, amount: u64) -> Result<()> {
+        require!(amount > 0, TipError::InvalidWithdrawAmount);
+
+        let now = Clock::get()?.unix_timestamp;
+        let vault = &mut ctx.accounts.vault;
+        let pending_withdrawal = &mut ctx.accounts.pending_withdrawal;
+
+        pending_withdrawal.vault = vault.key();
+        pending_withdrawal.authority = ctx.accounts.authority.key();
+        pending_withdrawal.destination = ctx.accounts.destination.key();
+        pending_withdrawal.amount = amount;
+        pending_withdrawal.executable_at = now
+            .checked_add(WITHDRAW_DELAY_SECONDS)
+            .ok_or(TipError::InvalidTimestamp)?;
+        pending_withdrawal.id = vault.pending_withdrawal_count;
+        pending_withdrawal.bump = ctx.bumps.pending_withdrawal;
+
+        vault.pending_withdrawal_count = vault
+            .pending_withdrawal_count
+            .checked_add(1)
+            .ok_or(TipError::InvalidPendingWithdrawal)?;
+
+        emit!(WithdrawalQueued {
+            authority: pending_withdrawal.authority,
+            vault: vault.key(),
+            hashed_user_id: vault.hashed_user_id,
+            pending_withdrawal: pending_withdrawal.key(),
+            id: pending_withdrawal.id,
+            destination: pending_withdrawal.destination,
+            amount,
+            executable_at: pending_withdrawal.executable_at,
+        });
+
+        Ok(())
+    }
+
+    pub fn execute_withdrawal(ctx: Context<ExecuteWithdrawal>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let pending_withdrawal = &ctx.accounts.pending_withdrawal;
+
+        require!(
+            now >= pending_withdrawal.executable_at,
+            TipError::WithdrawalNotReady
+        );
+        require!(
+            ctx.accounts.vault_token_account.amount >= pending_withdrawal.amount,
+            TipError::InsufficientVaultBalance
+        );
+
+        let vault_bump = ctx.bumps.vault;
+        transfer_from_vault(
+            ctx.accounts.token_program.to_account_info(),
+            ctx.accounts.vault_token_account.to_account_info(),
+            ctx.accounts.destination_token_account.to_account_info(),
+            ctx.accounts.vault.to_account_info(),
+            &ctx.accounts.vault.hashed_user_id,
+            vault_bump,
+            pending_withdrawal.amount,
+        )?;
+
+        emit!(WithdrawEvent {
+            authority: ctx.accounts.authority.key(),
+            vault: ctx.accounts.vault.key(),
+            vault_bump,
+            hashed_user_id: ctx.accounts.vault.hashed_user_id,
+            destination: ctx.accounts.destination.key(),
+            destination_token_account: ctx.accounts.destination_token_account.key(),
+            amount: pending_withdrawal.amount,
+        });
+
+        Ok(())
+    }
+
+    pub fn cancel_withdrawal(ctx: Context<CancelWithdrawal>) -> Result<()> {
+        let pending_withdrawal = &ctx.accounts.pending_withdrawal;
+
+        emit!(WithdrawalCancelled {
+            authority: pending_withdrawal.authority,
+            vault: pending_withdrawal.vault,
+            hashed_user_id: ctx.accounts.vault.hashed_user_id,
+            pending_withdrawal: pending_withdrawal.key(),
+            id: pending_withdrawal.id,
+            amount: pending_withdrawal.amount,
+        });
+
+        Ok(())
+    }
+
+    pub fn withdraw_fee(ctx: Context<WithdrawFee>, amount: u64) -> Result<()> {
+        require!(amount > 0, TipError::InvalidWithdrawAmount);
+
+        require!(
+            ctx.accounts.fee_vault_token_account.amount >= amount,
+            TipError::InsufficientVaultBalance
+        );
+
+        let fee_vault_bump = ctx.accounts.fee_vault.bump;
+        let config_key = ctx.accounts.config.key();
+        let fee_vault_seeds: [&[u8]; 3] = [b"fee_vault", config_key.as_ref(), &[fee_vault_bump]];
+        let signer_seeds: [&[&[u8]]; 1] = [&fee_vault_seeds];
+
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.fee_vault_token_account.to_account_info(),
+            to: ctx.accounts.destination_token_account.to_account_info(),
+            authority: ctx.accounts.fee_vault.to_account_info(),
+        };
+
+        let cpi_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            cpi_accounts,
+            &signer_seeds,
+        );
+
+        token::transfer(cpi_ctx, amount)?;
+
+        Ok(())
+    }
+
+    pub fn set_relayer(ctx: Context<SetRelayer>, new_relayer: Pubkey) -> Result<()> {
+        require!(new_relayer != Pubkey::default(), TipError::InvalidRelayer);
+
+        let config = &mut ctx.accounts.config;
+        if config.relayer != new_relayer {
+            config.previous_relayer = config.relayer;
+            config.relayer = new_relayer;
+        }
+        Ok(())
+    }
+
+    pub fn set_fee_rate(ctx: Context<SetFeeRate>, fee_bps: u16) -> Result<()> {
+        require!(fee_bps <= MAX_FEE_BPS, TipError::InvalidFeeBps);
+        ctx.accounts.config.fee_bps = fee_bps;
+        Ok(())
+    }
+
+    pub fn set_rate_limit(
+        ctx: Context<SetRateLimit>,
+        max_tips: u64,
+        window_seconds: i64,
+    ) -> Result<()> {
+        check_rate_limit(max_tips, window_seconds)?;
+
+        let config = &mut ctx.accounts.config;
+        config.rate_limit_max_tips = max_tips;
+        config.rate_limit_window_seconds = window_seconds;
+
+        emit!(RateLimitUpdated {
+            max_tips,
+            window_seconds,
+        });
+
+        Ok(())
+    }
+
+    pub fn set_vault_rate_limit(
+        ctx: Context<SetVaultRateLimit>,
+        overridden: bool,
+        max_tips: u64,
+        window_seconds: i64,
+    ) -> Result<()> {
+        if overridden {
+            check_rate_limit(max_tips, window_seconds)?;
+        } else {
+            require!(
+                max_tips == 0 && window_seconds == 0,
+                TipError::InvalidRateLimit
+            );
+        }
+
+        let vault = &mut ctx.accounts.vault;
+        vault.rate_limit_override = overridden;
+        vault.rate_limit_max_tips = max_tips;
+        vault.rate_limit_window_seconds = window_seconds;
+        vault.tip_bucket_tokens = 0;
+        vault.tip_bucket_updated_at = 0;
+
+        emit!(VaultRateLimitUpdated {
+            vault: vault.key(),
+            hashed_user_id: vault.hashed_user_id,
+            overridden,
+            max_tips,
+            window_seconds,
+        });
+
+        Ok(())
+    }
+
+    pub fn set_min_tip_amounts(
+        ctx: Context<SetMinTipAmounts>,
+        min_tip_amount: u64,
+        min_new_vault_tip_amount: u64,
+    ) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        config.min_tip_amount = min_tip_amount;
+        config.min_new_vault_tip_amount = min_new_vault_tip_amount;
+
+        emit!(MinTipAmountsUpdated {
+            min_tip_amount,
+            min_new_vault_tip_amount,
+        });
+
+        Ok(())
+    }
+
+    pub fn set_tip_dispute_window(
+        ctx: Context<SetTipDisputeWindow>,
+        window_seconds: i64,
+    ) -> Result<()> {
+        require!(
+            (0..=MAX_TIP_DISPUTE_WINDOW_SECONDS).contains(&window_seconds),
+            TipError::InvalidDisputeWindow
+        );
+
+        ctx.accounts.config.tip_dispute_window_seconds = window_seconds;
+
+        emit!(TipDisputeWindowUpdated { window_seconds });
+
+        Ok(())
+    }
+
+    pub fn set_claim_authority(
+        ctx: Context<SetClaimAuthority>,
+        new_claim_authority: Pubkey,
+    ) -> Result<()> {
+        require!(
+            new_claim_authority != Pubkey::default(),
+            TipError::InvalidClaimAuthority
+        );
+        ctx.accounts.config.claim_authority = new_claim_authority;
+        Ok(())
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct InitializeConfigArgs {
+    pub relayer: Pubkey,
+    pub token_mint: Pubkey,
+    pub fee_bps: u16,
+    pub claim_authority: Pubkey,
+}
+
+#[derive(Accounts)]
+pub struct InitializeConfig<'info> {
+    #[account(mut)]
+    pub upgrade_authority: Signer<'info>,
+    /// CHECK: Verified as the deployed Tipcoin program
+    #[account(address = crate::ID)]
+    pub program: AccountInfo<'info>,
+    /// CHECK: Must be the ProgramData account for `program`
+    #[account(mut)]
+    pub program_data: AccountInfo<'info>,
+    #[account(
+        init,
+        payer = upgrade_authority,
+        space = Config::SPACE,
+        seeds = [b"config"],
+        bump
+    )]
+    pub config: Account<'info, Config>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(hashed_user_id: [u8; 32])]
+pub struct ClaimVault<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(mut)]
+    pub claim_authority: Signer<'info>,
+    #[account(
+        seeds = [b"config"],
+        bump,
+        constraint = config.token_mint != Pubkey::default() @ TipError::InvalidTokenMint,
+        constraint = config.claim_authority != Pubkey::default() @ TipError::InvalidClaimAuthority
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = Vault::SPACE,
+        seeds = [b"vault", hashed_user_id.as_ref()],
+        bump
+    )]
+    pub vault: Account<'info, Vault>,
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = Allowance::SPACE,
+        seeds = [b"allowance", hashed_user_id.as_ref(), config.relayer.as_ref()],
+        bump
+    )]
+    pub allowance: Account<'info, Allowance>,
+    pub system_program: Program<'info, System>,
+    #[account(address = sysvar::rent::ID)]
+    pub rent: Sysvar<'info, Rent>,
+    #[account(constraint = token_mint.key() == config.token_mint @ TipError::InvalidTokenMint)]
+    pub token_mint: Account<'info, Mint>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct WithdrawIntent {
+    pub vault: Pubkey,
+    pub destination: Pubkey,
+    pub amount: u64,
+    pub relayer_fee: u64,
+    pub nonce: u64,
+    pub expires_at: i64,
+}
+
+#[derive(Accounts)]
+pub struct Deposit<'info> {
+    #[account(seeds = [b"config"], bump)]
+    pub config: Account<'info, Config>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"vault", vault.hashed_user_id.as_ref()],
+        bump,
+        has_one = authority
+    )]
+    pub vault: Account<'info, Vault>,
+    #[account(
+        mut,
+        constraint = authority_token_account.owner == authority.key() @ TipError::InvalidAuthority,
+        constraint = authority_token_account.mint == config.token_mint @ TipError::InvalidTokenMint
+    )]
+    pub authority_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = vault_token_account.owner == vault.key() @ TipError::InvalidVaultAuthority,
+        constraint = vault_token_account.mint == config.token_mint @ TipError::InvalidTokenMint
+    )]
+    pub vault_token_account: Account<'info, TokenAccount>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+#[instruction(hashed_user_id: [u8; 32])]
+pub struct DepositTo<'info> {
+    #[account(seeds = [b"config"], bump)]
+    pub config: Account<'info, Config>,
+    #[account(mut)]
+    pub funder: Signer<'info>,
+    #[account(
+        init_if_needed,
+        payer = funder,
+        space = Vault::SPACE,
+        seeds = [b"vault", hashed_user_id.as_ref()],
+        bump
+    )]
+    pub vault: Account<'info, Vault>,
+    #[account(
+        mut,
+        constraint = funder_token_account.owner == funder.key() @ TipError::InvalidAuthority,
+        constraint = funder_token_account.mint == config.token_mint @ TipError::InvalidTokenMint
+    )]
+    pub funder_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = vault_token_account.owner == vault.key() @ TipError::InvalidVaultAuthority,
+        constraint = vault_token_account.mint == config.token_mint @ TipError::InvalidTokenMint
+    )]
+    pub vault_token_account: Account<'info, TokenAccount>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct ApproveAllowanceArgs {
+    pub spender: Pubkey,
+    pub amount: u64,
+    pub period_seconds: i64,
+    pub expires_at: i64,
+    pub max_per_tip: u64,
+    pub valid_until: i64,
+}
+
+#[derive(Accounts)]
+#[instruction(args: ApproveAllowanceArgs)]
+pub struct ApproveAllowance<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        seeds = [b"vault", vault.hashed_user_id.as_ref()],
+        bump,
+        has_one = authority
+    )]
+    pub vault: Account<'info, Vault>,
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = Allowance::SPACE,
+        seeds = [b"allowance", vault.hashed_user_id.as_ref(), args.spender.as_ref()],
+        bump
+    )]
+    pub allowance: Account<'info, Allowance>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RevokeAllowance<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [
+            b"allowance",
+            allowance.hashed_user_id.as_ref(),
+            allowance.spender.as_ref()
+        ],
+        bump,
+        has_one = authority
+    )]
+    pub allowance: Account<'info, Allowance>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub enum AllowanceDelta {
+    Increase(u64),
+    Decrease(u64),
+}
+
+#[derive(Accounts)]
+pub struct AdjustAllowance<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [
+            b"allowance",
+            allowance.hashed_user_id.as_ref(),
+            allowance.spender.as_ref()
+        ],
+        bump,
+        has_one = authority
+    )]
+    pub allowance: Account<'info, Allowance>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateLegacyAllowance<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(seeds = [b"config"], bump)]
+    pub config: Account<'info, Config>,
+    #[account(
+        seeds = [b"vault", vault.hashed_user_id.as_ref()],
+        bump,
+        has_one = authority
+    )]
+    pub vault: Account<'info, Vault>,
+    #[account(
+        mut,
+        close = authority,
+        seeds = [b"allowance", vault.hashed_user_id.as_ref()],
+        bump,
+        constraint = legacy_allowance.to_account_info().data_len() == LegacyAllowance::SPACE
+            @ TipError::InvalidMigrationAccount
+    )]
+    pub legacy_allowance: Account<'info, LegacyAllowance>,
+    #[account(
+        init,
+        payer = authority,
+        space = Allowance::SPACE,
+        seeds = [b"allowance", vault.hashed_user_id.as_ref(), config.relayer.as_ref()],
+        bump
+    )]
+    pub allowance: Account<'info, Allowance>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateRelayerAllowance<'info> {
+    #[account(
+        seeds = [b"config"],
+        bump,
+        constraint = config.previous_relayer != Pubkey::default() @ TipError::InvalidRelayer,
+        constraint = config.previous_relayer != config.relayer @ TipError::InvalidRelayer
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        mut,
+        constraint = relayer.key() == config.relayer @ TipError::InvalidRelayer
+    )]
+    pub relayer: Signer<'info>,
+    /// CHECK: Receives the previous allowance rent; must be its authority
+    #[account(mut, address = previous_allowance.authority @ TipError::InvalidAuthority)]
+    pub authority: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        close = authority,
+        seeds = [
+            b"allowance",
+            previous_allowance.hashed_user_id.as_ref(),
+            config.previous_relayer.as_ref()
+        ],
+        bump
+    )]
+    pub previous_allowance: Account<'info, Allowance>,
+    #[account(
+        init,
+        payer = relayer,
+        space = Allowance::SPACE,
+        seeds = [
+            b"allowance",
+            previous_allowance.hashed_user_id.as_ref(),
+            config.relayer.as_ref()
+        ],
+        bump
+    )]
+    pub allowance: Account<'info, Allowance>,
+    #[account(
+        mut,
+        close = authority,
+        seeds = [b"recipient_rules", previous_allowance.key().as_ref()],
+        bump = previous_recipient_rules.bump
+    )]
+    pub previous_recipient_rules: Option<Account<'info, RecipientRules>>,
+    #[account(
+        init,
+        payer = relayer,
+        space = RecipientRules::SPACE,
+        seeds = [b"recipient_rules", allowance.key().as_ref()],
+        bump
+    )]
+    pub recipient_rules: Option<Account<'info, RecipientRules>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct AddRecipientRule<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [
+            b"allowance",
+            allowance.hashed_user_id.as_ref(),
+            allowance.spender.as_ref()
+        ],
+        bump,
+        has_one = authority
+    )]
+    pub allowance: Account<'info, Allowance>,
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = RecipientRules::SPACE,
+        seeds = [b"recipient_rules", allowance.key().as_ref()],
+        bump
+    )]
+    pub recipient_rules: Account<'info, RecipientRules>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RemoveRecipientRule<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        seeds = [
+            b"allowance",
+            allowance.hashed_user_id.as_ref(),
+            allowance.spender.as_ref()
+        ],
+        bump,
+        has_one = authority
+    )]
+    pub allowance: Account<'info, Allowance>,
+    #[account(
+        mut,
+        seeds = [b"recipient_rules", allowance.key().as_ref()],
+        bump = recipient_rules.bump,
+        has_one = allowance @ TipError::MissingRecipientRules
+    )]
+    pub recipient_rules: Account<'info, RecipientRules>,
+}
+
+#[derive(Accounts)]
+pub struct ClearRecipientRules<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [
+            b"allowance",
+            allowance.hashed_user_id.as_ref(),
+            allowance.spender.as_ref()
+        ],
+        bump,
+        has_one = authority
+    )]
+    pub allowance: Account<'info, Allowance>,
+    #[account(
+        mut,
+        close = authority,
+        seeds = [b"recipient_rules", allowance.key().as_ref()],
+        bump = recipient_rules.bump,
+        has_one = allowance @ TipError::MissingRecipientRules
+    )]
+    pub recipient_rules: Account<'info, RecipientRules>,
+}
+
+#[derive(Accounts)]
+#[instruction(
+    amount: u64,
+    tip_id: [u8; 32],
+    sender_nonce: u64,
+    memo: Option<String>,
+    recipient_hashed_user_id: [u8; 32]
+)]
+pub struct Tip<'info> {
+    #[account(mut, seeds = [b"config"], bump)]
+    pub config: Account<'info, Config>,
+    #[account(mut)]
+    pub relayer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"vault", sender_vault.hashed_user_id.as_ref()],
+        bump
+    )]
+    pub sender_vault: Account<'info, Vault>,
+    #[account(
+        mut,
+        seeds = [
+            b"allowance",
+            sender_vault.hashed_user_id.as_ref(),
+            relayer.key().as_ref()
+        ],
+        bump
+    )]
+    pub sender_allowance: Account<'info, Allowance>,
+    #[account(
+        seeds = [b"recipient_rules", sender_allowance.key().as_ref()],
+        bump = recipient_rules.bump
+    )]
+    pub recipient_rules: Option<Account<'info, RecipientRules>>,
+    #[account(
+        init_if_needed,
+        payer = relayer,
+        space = Vault::SPACE,
+        seeds = [b"vault", recipient_hashed_user_id.as_ref()],
+        bump
+    )]
+    pub recipient_vault: Account<'info, Vault>,
+    #[account(
+        init_if_needed,
+        payer = relayer,
+        space = FeeVault::SPACE,
+        seeds = [b"fee_vault", config.key().as_ref()],
+        bump
+    )]
+    pub fee_vault: Account<'info, FeeVault>,
+    #[account(
+        init_if_needed,
+        payer = relayer,
+        space = TipReceipt::SPACE,
+        seeds = [b"tip_receipt", sender_vault.key().as_ref(), tip_id.as_ref()],
+        bump
+    )]
+    pub tip_receipt: Account<'info, TipReceipt>,
+    #[account(
+        mut,
+        constraint = sender_vault_token_account.owner == sender_vault.key() @ TipError::InvalidVaultAuthority,
+        constraint = sender_vault_token_account.mint == config.token_mint @ TipError::InvalidTokenMint
+    )]
+    pub sender_vault_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = recipient_vault_token_account.owner == recipient_vault.key() @ TipError::InvalidVaultAuthority,
+        constraint = recipient_vault_token_account.mint == config.token_mint @ TipError::InvalidTokenMint
+    )]
+    pub recipient_vault_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = fee_vault_token_account.owner == fee_vault.key() @ TipError::InvalidVaultAuthority,
+        constraint = fee_vault_token_account.mint == config.token_mint @ TipError::InvalidTokenMint
+    )]
+    pub fee_vault_token_account: Account<'info, TokenAccount>,
+    /// CHECK: Verified as the instructions sysvar
+    #[account(address = instructions_sysvar_id::ID)]
+    pub instructions_sysvar: Option<UncheckedAccount<'info>>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct TipIntent {
+    pub sender_vault: Pubkey,
+    pub spender: Pubkey,
+    pub recipient_hashed_user_id: [u8; 32],
+    pub amount: u64,
+    pub tip_id: [u8; 32],
+    pub sender_nonce: u64,
+    pub valid_until: i64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct WalletTipIntent {
+    pub sender_vault: Pubkey,
+    pub spender: Pubkey,
+    pub destination_token_account: Pubkey,
+    pub amount: u64,
+    pub tip_id: [u8; 32],
+    pub sender_nonce: u64,
+    pub valid_until: i64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct PoolContributionIntent {
+    pub sender_vault: Pubkey,
+    pub spender: Pubkey,
+    pub pool: Pubkey,
+    pub amount: u64,
+    pub sender_nonce: u64,
+    pub valid_until: i64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct InvoicePaymentIntent {
+    pub sender_vault: Pubkey,
+    pub spender: Pubkey,
+    pub invoice: Pubkey,
+    pub amount: u64,
+    pub sender_nonce: u64,
+    pub valid_until: i64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct TipIntentRecipient {
+    pub recipient_hashed_user_id: [u8; 32],
+    pub amount: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct TipManyIntent {
+    pub sender_vault: Pubkey,
+    pub spender: Pubkey,
+    pub recipients: Vec<TipIntentRecipient>,
+    pub tip_id: [u8; 32],
+    pub sender_nonce: u64,
+    pub valid_until: i64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct TipManyRecipient {
+    pub recipient_hashed_user_id: [u8; 32],
+    pub amount: u64,
+    pub recipient_vault_bump: u8,
+}
+
+#[derive(Accounts)]
+#[instruction(
+    amount: u64,
+    tip_id: [u8; 32],
+    sender_nonce: u64,
+    memo: Option<String>,
+    recipient_hashed_user_id: [u8; 32]
+)]
+pub struct EscrowTip<'info> {
+    #[account(seeds = [b"config"], bump)]
+    pub config: Account<'info, Config>,
+    #[account(mut)]
+    pub relayer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"vault", sender_vault.hashed_user_id.as_ref()],
+        bump
+    )]
+    pub sender_vault: Account<'info, Vault>,
+    #[account(
+        mut,
+        seeds = [
+            b"allowance",
+            sender_vault.hashed_user_id.as_ref(),
+            relayer.key().as_ref()
+        ],
+        bump
+    )]
+    pub sender_allowance: Account<'info, Allowance>,
+    #[account(
+        seeds = [b"recipient_rules", sender_allowance.key().as_ref()],
+        bump = recipient_rules.bump
+    )]
+    pub recipient_rules: Option<Account<'info, RecipientRules>>,
+    #[account(
+        init_if_needed,
+        payer = relayer,
+        space = Vault::SPACE,
+        seeds = [b"vault", recipient_hashed_user_id.as_ref()],
+        bump
+    )]
+    pub recipient_vault: Account<'info, Vault>,
+    #[account(
+        init_if_needed,
+        payer = relayer,
+        space = FeeVault::SPACE,
+        seeds = [b"fee_vault", config.key().as_ref()],
+        bump
+    )]
+    pub fee_vault: Account<'info, FeeVault>,
+    #[account(
+        init_if_needed,
+        payer = relayer,
+        space = TipEscrow::SPACE,
+        seeds = [b"tip_escrow", config.key().as_ref()],
+        bump
+    )]
+    pub tip_escrow: Account<'info, TipEscrow>,
+    #[account(
+        init,
+        payer = relayer,
+        space = PendingTip::SPACE,
+        seeds = [b"pending_tip", sender_vault.key().as_ref(), tip_id.as_ref()],
+        bump
+    )]
+    pub pending_tip: Account<'info, PendingTip>,
+    #[account(
+        init_if_needed,
+        payer = relayer,
+        space = TipReceipt::SPACE,
+        seeds = [b"tip_receipt", sender_vault.key().as_ref(), tip_id.as_ref()],
+        bump
+    )]
+    pub tip_receipt: Account<'info, TipReceipt>,
+    #[account(
+        mut,
+        constraint = sender_vault_token_account.owner == sender_vault.key() @ TipError::InvalidVaultAuthority,
+        constraint = sender_vault_token_account.mint == config.token_mint @ TipError::InvalidTokenMint
+    )]
+    pub sender_vault_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = tip_escrow_token_account.owner == tip_escrow.key() @ TipError::InvalidVaultAuthority,
+        constraint = tip_escrow_token_account.mint == config.token_mint @ TipError::InvalidTokenMint
+    )]
+    pub tip_escrow_token_account: Account<'info, TokenAccount>,
+    /// CHECK: Verified as the instructions sysvar
+    #[account(address = instructions_sysvar_id::ID)]
+    pub instructions_sysvar: Option<UncheckedAccount<'info>>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct FinalizeTip<'info> {
+    #[account(seeds = [b"config"], bump)]
+    pub config: Account<'info, Config>,
+    /// CHECK: Receives the pending tip's rent; must be the relayer that paid for it
+    #[account(mut, address = pending_tip.relayer @ TipError::InvalidPendingTip)]
+    pub relayer: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [
+            b"pending_tip",
+            pending_tip.sender_vault.as_ref(),
+            pending_tip.tip_id.as_ref()
+        ],
+        bump = pending_tip.bump,
+        close = relayer
+    )]
+    pub pending_tip: Account<'info, PendingTip>,
+    #[account(
+        seeds = [b"tip_escrow", config.key().as_ref()],
+        bump = tip_escrow.bump,
+        constraint = tip_escrow.config == config.key() @ TipError::InvalidTipEscrow
+    )]
+    pub tip_escrow: Account<'info, TipEscrow>,
+    #[account(
+        mut,
+        constraint = tip_escrow_token_account.owner == tip_escrow.key() @ TipError::InvalidVaultAuthority,
+        constraint = tip_escrow_token_account.mint == config.token_mint @ TipError::InvalidTokenMint
+    )]
+    pub tip_escrow_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = recipient_vault_token_account.owner == pending_tip.recipient_vault @ TipError::InvalidVaultAuthority,
+        constraint = recipient_vault_token_account.mint == config.token_mint @ TipError::InvalidTokenMint
+    )]
+    pub recipient_vault_token_account: Account<'info, TokenAccount>,
+    #[account(
+        seeds = [b"fee_vault", config.key().as_ref()],
+        bump = fee_vault.bump,
+        constraint = fee_vault.config == config.key() @ TipError::InvalidFeeVault
+    )]
+    pub fee_vault: Account<'info, FeeVault>,
+    #[account(
+        mut,
+        constraint = fee_vault_token_account.owner == fee_vault.key() @ TipError::InvalidVaultAuthority,
+        constraint = fee_vault_token_account.mint == config.token_mint @ TipError::InvalidTokenMint
+    )]
+    pub fee_vault_token_account: Account<'info, TokenAccount>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct ReverseTip<'info> {
+    #[account(seeds = [b"config"], bump)]
+    pub config: Account<'info, Config>,
+    pub authority: Signer<'info>,
+    /// CHECK: Receives the pending tip's rent; must be the relayer that paid for it
+    #[account(mut, address = pending_tip.relayer @ TipError::InvalidPendingTip)]
+    pub relayer: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [
+            b"pending_tip",
+            pending_tip.sender_vault.as_ref(),
+            pending_tip.tip_id.as_ref()
+        ],
+        bump = pending_tip.bump,
+        close = relayer
+    )]
+    pub pending_tip: Account<'info, PendingTip>,
+    #[account(
+        mut,
+        seeds = [b"vault", sender_vault.hashed_user_id.as_ref()],
+        bump,
+        address = pending_tip.sender_vault @ TipError::InvalidPendingTip
+    )]
+    pub sender_vault: Account<'info, Vault>,
+    #[account(
+        mut,
+        address = pending_tip.sender_allowance @ TipError::InvalidPendingTip
+    )]
+    pub sender_allowance: Account<'info, Allowance>,
+    #[account(
+        seeds = [b"tip_escrow", config.key().as_ref()],
+        bump = tip_escrow.bump,
+        constraint = tip_escrow.config == config.key() @ TipError::InvalidTipEscrow
+    )]
+    pub tip_escrow: Account<'info, TipEscrow>,
+    #[account(
+        mut,
+        constraint = tip_escrow_token_account.owner == tip_escrow.key() @ TipError::InvalidVaultAuthority,
+        constraint = tip_escrow_token_account.mint == config.token_mint @ TipError::InvalidTokenMint
+    )]
+    pub tip_escrow_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = sender_vault_token_account.owner == pending_tip.sender_vault @ TipError::InvalidVaultAuthority,
+        constraint = sender_vault_token_account.mint == config.token_mint @ TipError::InvalidTokenMint
+    )]
+    pub sender_vault_token_account: Account<'info, TokenAccount>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+#[instruction(amount: u64, tip_id: [u8; 32])]
+pub struct TipToWallet<'info> {
+    #[account(seeds = [b"config"], bump)]
+    pub config: Account<'info, Config>,
+    #[account(mut)]
+    pub relayer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"vault", sender_vault.hashed_user_id.as_ref()],
+        bump
+    )]
+    pub sender_vault: Account<'info, Vault>,
+    #[account(
+        mut,
+        seeds = [
+            b"allowance",
+            sender_vault.hashed_user_id.as_ref(),
+            relayer.key().as_ref()
+        ],
+        bump
+    )]
+    pub sender_allowance: Account<'info, Allowance>,
+    #[account(
+        init_if_needed,
+        payer = relayer,
+        space = FeeVault::SPACE,
+        seeds = [b"fee_vault", config.key().as_ref()],
+        bump
+    )]
+    pub fee_vault: Account<'info, FeeVault>,
+    #[account(
+        init_if_needed,
+        payer = relayer,
+        space = TipReceipt::SPACE,
+        seeds = [b"tip_receipt", sender_vault.key().as_ref(), tip_id.as_ref()],
+        bump
+    )]
+    pub tip_receipt: Account<'info, TipReceipt>,
+    #[account(
+        mut,
+        constraint = sender_vault_token_account.owner == sender_vault.key() @ TipError::InvalidVaultAuthority,
+        constraint = sender_vault_token_account.mint == config.token_mint @ TipError::InvalidTokenMint
+    )]
+    pub sender_vault_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = destination_token_account.mint == config.token_mint @ TipError::InvalidTokenMint,
+        constraint = destination_token_account.key() != sender_vault_token_account.key() @ TipError::InvalidDestination
+    )]
+    pub destination_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = fee_vault_token_account.owner == fee_vault.key() @ TipError::InvalidVaultAuthority,
+        constraint = fee_vault_token_account.mint == config.token_mint @ TipError::InvalidTokenMint
+    )]
+    pub fee_vault_token_account: Account<'info, TokenAccount>,
+    /// CHECK: Verified as the instructions sysvar
+    #[account(address = instructions_sysvar_id::ID)]
+    pub instructions_sysvar: Option<UncheckedAccount<'info>>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(recipients: Vec<TipManyRecipient>, tip_id: [u8; 32])]
+pub struct TipMany<'info> {
+    #[account(seeds = [b"config"], bump)]
+    pub config: Account<'info, Config>,
+    #[account(mut)]
+    pub relayer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"vault", sender_vault.hashed_user_id.as_ref()],
+        bump
+    )]
+    pub sender_vault: Account<'info, Vault>,
+    #[account(
+        mut,
+        seeds = [
+            b"allowance",
+            sender_vault.hashed_user_id.as_ref(),
+            relayer.key().as_ref()
+        ],
+        bump
+    )]
+    pub sender_allowance: Account<'info, Allowance>,
+    #[account(
+        seeds = [b"recipient_rules", sender_allowance.key().as_ref()],
+        bump = recipient_rules.bump
+    )]
+    pub recipient_rules: Option<Account<'info, RecipientRules>>,
+    #[account(
+        init_if_needed,
+        payer = relayer,
+        space = FeeVault::SPACE,
+        seeds = [b"fee_vault", config.key().as_ref()],
+        bump
+    )]
+    pub fee_vault: Account<'info, FeeVault>,
+    #[account(
+        init_if_needed,
+        payer = relayer,
+        space = TipReceipt::SPACE,
+        seeds = [b"tip_receipt", sender_vault.key().as_ref(), tip_id.as_ref()],
+        bump
+    )]
+    pub tip_receipt: Account<'info, TipReceipt>,
+    #[account(
+        mut,
+        constraint = sender_vault_token_account.owner == sender_vault.key() @ TipError::InvalidVaultAuthority,
+        constraint = sender_vault_token_account.mint == config.token_mint @ TipError::InvalidTokenMint
+    )]
+    pub sender_vault_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = fee_vault_token_account.owner == fee_vault.key() @ TipError::InvalidVaultAuthority,
+        constraint = fee_vault_token_account.mint == config.token_mint @ TipError::InvalidTokenMint
+    )]
+    pub fee_vault_token_account: Account<'info, TokenAccount>,
+    /// CHECK: Verified as the instructions sysvar
+    #[account(address = instructions_sysvar_id::ID)]
+    pub instructions_sysvar: Option<UncheckedAccount<'info>>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(session_key: Pubkey)]
+pub struct CreateSession<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        seeds = [b"vault", vault.hashed_user_id.as_ref()],
+        bump,
+        has_one = authority
+    )]
+    pub vault: Account<'info, Vault>,
+    #[account(
+        init,
+        payer = authority,
+        space = Session::SPACE,
+        seeds = [b"session", vault.key().as_ref(), session_key.as_ref()],
+        bump
+    )]
+    pub session: Account<'info, Session>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RevokeSession<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        seeds = [b"vault", vault.hashed_user_id.as_ref()],
+        bump,
+        has_one = authority
+    )]
+    pub vault: Account<'info, Vault>,
+    #[account(
+        mut,
+        close = authority,
+        seeds = [b"session", vault.key().as_ref(), session.session_key.as_ref()],
+        bump = session.bump,
+        has_one = vault @ TipError::InvalidSessionKey,
+        has_one = authority @ TipError::InvalidVaultAuthority
+    )]
+    pub session: Account<'info, Session>,
+}
+
+#[derive(Accounts)]
+#[instruction(
+    amount: u64,
+    tip_id: [u8; 32],
+    sender_nonce: u64,
+    memo: Option<String>,
+    recipient_hashed_user_id: [u8; 32]
+)]
+pub struct TipWithSession<'info> {
+    #[account(seeds = [b"config"], bump)]
+    pub config: Account<'info, Config>,
+    #[account(mut)]
+    pub session_key: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"vault", sender_vault.hashed_user_id.as_ref()],
+        bump
+    )]
+    pub sender_vault: Account<'info, Vault>,
+    #[account(
+        mut,
+        seeds = [b"session", sender_vault.key().as_ref(), session_key.key().as_ref()],
+        bump = session.bump,
+        has_one = session_key @ TipError::InvalidSessionKey,
+        constraint = session.vault == sender_vault.key() @ TipError::InvalidSessionKey
+    )]
+    pub session: Account<'info, Session>,
+    #[account(
+        init_if_needed,
+        payer = session_key,
+        space = Vault::SPACE,
+        seeds = [b"vault", recipient_hashed_user_id.as_ref()],
+        bump
+    )]
+    pub recipient_vault: Account<'info, Vault>,
+    #[account(
+        init_if_needed,
+        payer = session_key,
+        space = FeeVault::SPACE,
+        seeds = [b"fee_vault", config.key().as_ref()],
+        bump
+    )]
+    pub fee_vault: Account<'info, FeeVault>,
+    #[account(
+        init_if_needed,
+        payer = session_key,
+        space = TipReceipt::SPACE,
+        seeds = [b"tip_receipt", sender_vault.key().as_ref(), tip_id.as_ref()],
+        bump
+    )]
+    pub tip_receipt: Account<'info, TipReceipt>,
+    #[account(
+        mut,
+        constraint = sender_vault_token_account.owner == sender_vault.key() @ TipError::InvalidVaultAuthority,
+        constraint = sender_vault_token_account.mint == config.token_mint @ TipError::InvalidTokenMint
+    )]
+    pub sender_vault_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = recipient_vault_token_account.owner == recipient_vault.key() @ TipError::InvalidVaultAuthority,
+        constraint = recipient_vault_token_account.mint == config.token_mint @ TipError::InvalidTokenMint
+    )]
+    pub recipient_vault_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = fee_vault_token_account.owner == fee_vault.key() @ TipError::InvalidVaultAuthority,
+        constraint = fee_vault_token_account.mint == config.token_mint @ TipError::InvalidTokenMint
+    )]
+    pub fee_vault_token_account: Account<'info, TokenAccount>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(recipient_hashed_user_id: [u8; 32])]
+pub struct CreateSubscription<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        seeds = [b"vault", vault.hashed_user_id.as_ref()],
+        bump,
+        has_one = authority
+    )]
+    pub vault: Account<'info, Vault>,
+    #[account(
+        init,
+        payer = authority,
+        space = Subscription::SPACE,
+        seeds = [
+            b"subscription",
+            vault.key().as_ref(),
+            recipient_hashed_user_id.as_ref()
+        ],
+        bump
+    )]
+    pub subscription: Account<'info, Subscription>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CancelSubscription<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        close = authority,
+        seeds = [
+            b"subscription",
+            subscription.sender_vault.as_ref(),
+            subscription.recipient_hashed_user_id.as_ref()
+        ],
+        bump = subscription.bump,
+        has_one = authority @ TipError::InvalidVaultAuthority
+    )]
+    pub subscription: Account<'info, Subscription>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteSubscription<'info> {
+    #[account(seeds = [b"config"], bump)]
+    pub config: Account<'info, Config>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [
+            b"subscription",
+            subscription.sender_vault.as_ref(),
+            subscription.recipient_hashed_user_id.as_ref()
+        ],
+        bump = subscription.bump
+    )]
+    pub subscription: Account<'info, Subscription>,
+    #[account(
+        mut,
+        seeds = [b"vault", sender_vault.hashed_user_id.as_ref()],
+        bump,
+        address = subscription.sender_vault @ TipError::InvalidSenderPda
+    )]
+    pub sender_vault: Account<'info, Vault>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = Vault::SPACE,
+        seeds = [b"vault", subscription.recipient_hashed_user_id.as_ref()],
+        bump
+    )]
+    pub recipient_vault: Account<'info, Vault>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = FeeVault::SPACE,
+        seeds = [b"fee_vault", config.key().as_ref()],
+        bump
+    )]
+    pub fee_vault: Account<'info, FeeVault>,
+    #[account(
+        mut,
+        constraint = sender_vault_token_account.owner == sender_vault.key() @ TipError::InvalidVaultAuthority,
+        constraint = sender_vault_token_account.mint == config.token_mint @ TipError::InvalidTokenMint
+    )]
+    pub sender_vault_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = recipient_vault_token_account.owner == recipient_vault.key() @ TipError::InvalidVaultAuthority,
+        constraint = recipient_vault_token_account.mint == config.token_mint @ TipError::InvalidTokenMint
+    )]
+    pub recipient_vault_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = fee_vault_token_account.owner == fee_vault.key() @ TipError::InvalidVaultAuthority,
+        constraint = fee_vault_token_account.mint == config.token_mint @ TipError::InvalidTokenMint
+    )]
+    pub fee_vault_token_account: Account<'info, TokenAccount>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(pool_id: [u8; 32])]
+pub struct CreatePool<'info> {
+    #[account(seeds = [b"config"], bump)]
+    pub config: Account<'info, Config>,
+    #[account(mut)]
+    pub creator: Signer<'info>,
+    #[account(
+        init,
+        payer = creator,
+        space = Pool::SPACE,
+        seeds = [b"pool", creator.key().as_ref(), pool_id.as_ref()],
+        bump
+    )]
+    pub pool: Account<'info, Pool>,
+    #[account(
+        init,
+        payer = creator,
+        seeds = [b"pool_token", pool.key().as_ref()],
+        bump,
+        token::mint = token_mint,
+        token::authority = pool
+    )]
+    pub pool_token_account: Account<'info, TokenAccount>,
+    #[account(constraint = token_mint.key() == config.token_mint @ TipError::InvalidTokenMint)]
+    pub token_mint: Account<'info, Mint>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ContributeToPool<'info> {
+    #[account(seeds = [b"config"], bump)]
+    pub config: Account<'info, Config>,
+    #[account(mut)]
+    pub relayer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"vault", sender_vault.hashed_user_id.as_ref()],
+        bump
+    )]
+    pub sender_vault: Account<'info, Vault>,
+    #[account(
+        mut,
+        seeds = [
+            b"allowance",
+            sender_vault.hashed_user_id.as_ref(),
+            relayer.key().as_ref()
+        ],
+        bump
+    )]
+    pub sender_allowance: Account<'info, Allowance>,
+    #[account(
+        seeds = [b"recipient_rules", sender_allowance.key().as_ref()],
+        bump = recipient_rules.bump
+    )]
+    pub recipient_rules: Option<Account<'info, RecipientRules>>,
+    #[account(
+        mut,
+        seeds = [b"pool", pool.creator.as_ref(), pool.pool_id.as_ref()],
+        bump = pool.bump
+    )]
+    pub pool: Account<'info, Pool>,
+    #[account(
+        init_if_needed,
+        payer = relayer,
+        space = PoolContribution::SPACE,
+        seeds = [
+            b"pool_contribution",
+            pool.key().as_ref(),
+            sender_vault.key().as_ref()
+        ],
+        bump
+    )]
+    pub pool_contribution: Account<'info, PoolContribution>,
+    #[account(
+        mut,
+        seeds = [b"pool_token", pool.key().as_ref()],
+        bump
+    )]
+    pub pool_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = sender_vault_token_account.owner == sender_vault.key() @ TipError::InvalidVaultAuthority,
+        constraint = sender_vault_token_account.mint == config.token_mint @ TipError::InvalidTokenMint
+    )]
+    pub sender_vault_token_account: Account<'info, TokenAccount>,
+    /// CHECK: Verified as the instructions sysvar
+    #[account(address = instructions_sysvar_id::ID)]
+    pub instructions_sysvar: Option<UncheckedAccount<'info>>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimPool<'info> {
+    #[account(seeds = [b"config"], bump)]
+    pub config: Account<'info, Config>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub authority: Signer<'info>,
+    /// CHECK: Receives the pool token account's rent; must be the pool creator
+    #[account(mut, address = pool.creator @ TipError::InvalidPoolContribution)]
+    pub creator: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [b"pool", pool.creator.as_ref(), pool.pool_id.as_ref()],
+        bump = pool.bump
+    )]
+    pub pool: Account<'info, Pool>,
+    #[account(
+        mut,
+        seeds = [b"pool_token", pool.key().as_ref()],
+        bump
+    )]
+    pub pool_token_account: Account<'info, TokenAccount>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = Vault::SPACE,
+        seeds = [b"vault", pool.recipient_hashed_user_id.as_ref()],
+        bump
+    )]
+    pub recipient_vault: Account<'info, Vault>,
+    #[account(
+        mut,
+        constraint = recipient_vault_token_account.owner == recipient_vault.key() @ TipError::InvalidVaultAuthority,
+        constraint = recipient_vault_token_account.mint == config.token_mint @ TipError::InvalidTokenMint
+    )]
+    pub recipient_vault_token_account: Account<'info, TokenAccount>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = FeeVault::SPACE,
+        seeds = [b"fee_vault", config.key().as_ref()],
+        bump
+    )]
+    pub fee_vault: Account<'info, FeeVault>,
+    #[account(
+        mut,
+        constraint = fee_vault_token_account.owner == fee_vault.key() @ TipError::InvalidVaultAuthority,
+        constraint = fee_vault_token_account.mint == config.token_mint @ TipError::InvalidTokenMint
+    )]
+    pub fee_vault_token_account: Account<'info, TokenAccount>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RefundPoolContribution<'info> {
+    /// CHECK: Receives the contribution record's rent; must be the account that paid for it
+    #[account(mut, address = pool_contribution.payer @ TipError::InvalidPoolContribution)]
+    pub payer: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [b"pool", pool.creator.as_ref(), pool.pool_id.as_ref()],
+        bump = pool.bump
+    )]
+    pub pool: Account<'info, Pool>,
+    #[account(
+        mut,
+        close = payer,
+        seeds = [
+            b"pool_contribution",
+            pool.key().as_ref(),
+            pool_contribution.sender_vault.as_ref()
+        ],
+        bump = pool_contribution.bump,
+        constraint = pool_contribution.pool == pool.key() @ TipError::InvalidPoolContribution
+    )]
+    pub pool_contribution: Account<'info, PoolContribution>,
+    #[account(
+        mut,
+        seeds = [b"pool_token", pool.key().as_ref()],
+        bump
+    )]
+    pub pool_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = sender_vault_token_account.owner == pool_contribution.sender_vault @ TipError::InvalidVaultAuthority,
+        constraint = sender_vault_token_account.mint == pool.token_mint @ TipError::InvalidTokenMint
+    )]
+    pub sender_vault_token_account: Account<'info, TokenAccount>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct ClosePoolContribution<'info> {
+    /// CHECK: Receives the contribution record's rent; must be the account that paid for it
+    #[account(mut, address = pool_contribution.payer @ TipError::InvalidPoolContribution)]
+    pub payer: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [b"pool", pool.creator.as_ref(), pool.pool_id.as_ref()],
+        bump = pool.bump
+    )]
+    pub pool: Account<'info, Pool>,
+    #[account(
+        mut,
+        close = payer,
+        seeds = [
+            b"pool_contribution",
+            pool.key().as_ref(),
+            pool_contribution.sender_vault.as_ref()
+        ],
+        bump = pool_contribution.bump,
+        constraint = pool_contribution.pool == pool.key() @ TipError::InvalidPoolContribution
+    )]
+    pub pool_contribution: Account<'info, PoolContribution>,
+}
+
+#[derive(Accounts)]
+pub struct ClosePool<'info> {
+    /// CHECK: Receives the pool's rent; must be the pool creator
+    #[account(mut, address = pool.creator @ TipError::InvalidPoolContribution)]
+    pub creator: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        close = creator,
+        seeds = [b"pool", pool.creator.as_ref(), pool.pool_id.as_ref()],
+        bump = pool.bump
+    )]
+    pub pool: Account<'info, Pool>,
+    #[account(
+        mut,
+        seeds = [b"pool_token", pool.key().as_ref()],
+        bump
+    )]
+    pub pool_token_account: Option<Account<'info, TokenAccount>>,
+    #[account(
+        mut,
+        constraint = creator_token_account.owner == pool.creator @ TipError::InvalidAuthority,
+        constraint = creator_token_account.mint == pool.token_mint @ TipError::InvalidTokenMint
+    )]
+    pub creator_token_account: Option<Account<'info, TokenAccount>>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+#[instruction(bounty_id: [u8; 32])]
+pub struct CreateBounty<'info> {
+    #[account(seeds = [b"config"], bump)]
+    pub config: Account<'info, Config>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"vault", creator_vault.hashed_user_id.as_ref()],
+        bump,
+        has_one = authority
+    )]
+    pub creator_vault: Account<'info, Vault>,
+    #[account(
+        mut,
+        constraint = creator_vault_token_account.owner == creator_vault.key() @ TipError::InvalidVaultAuthority,
+        constraint = creator_vault_token_account.mint == config.token_mint @ TipError::InvalidTokenMint
+    )]
+    pub creator_vault_token_account: Account<'info, TokenAccount>,
+    #[account(
+        init,
+        payer = authority,
+        space = Bounty::SPACE,
+        seeds = [b"bounty", creator_vault.key().as_ref(), bounty_id.as_ref()],
+        bump
+    )]
+    pub bounty: Account<'info, Bounty>,
+    #[account(
+        init,
+        payer = authority,
+        seeds = [b"bounty_token", bounty.key().as_ref()],
+        bump,
+        token::mint = token_mint,
+        token::authority = bounty
+    )]
+    pub bounty_token_account: Account<'info, TokenAccount>,
+    #[account(constraint = token_mint.key() == config.token_mint @ TipError::InvalidTokenMint)]
+    pub token_mint: Account<'info, Mint>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CancelBounty<'info> {
+    #[account(mut)]
+    pub creator: Signer<'info>,
+    #[account(
+        mut,
+        close = creator,
+        seeds = [
+            b"bounty",
+            bounty.creator_vault.as_ref(),
+            bounty.bounty_id.as_ref()
+        ],
+        bump = bounty.bump,
+        has_one = creator @ TipError::InvalidAuthority
+    )]
+    pub bounty: Account<'info, Bounty>,
+    #[account(
+        mut,
+        seeds = [b"bounty_token", bounty.key().as_ref()],
+        bump
+    )]
+    pub bounty_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        seeds = [b"vault", creator_vault.hashed_user_id.as_ref()],
+        bump,
+        address = bounty.creator_vault @ TipError::InvalidVaultAuthority
+    )]
+    pub creator_vault: Account<'info, Vault>,
+    #[account(
+        mut,
+        constraint = creator_vault_token_account.owner == bounty.creator_vault @ TipError::InvalidVaultAuthority,
+        constraint = creator_vault_token_account.mint == bounty_token_account.mint @ TipError::InvalidTokenMint
+    )]
+    pub creator_vault_token_account: Account<'info, TokenAccount>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+#[instruction(recipient_hashed_user_id: [u8; 32])]
+pub struct AwardBounty<'info> {
+    #[account(seeds = [b"config"], bump)]
+    pub config: Account<'info, Config>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    /// CHECK: Receives the bounty's rent; must be the bounty creator
+    #[account(mut, address = bounty.creator @ TipError::InvalidAuthority)]
+    pub creator: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        close = creator,
+        seeds = [
+            b"bounty",
+            bounty.creator_vault.as_ref(),
+            bounty.bounty_id.as_ref()
+        ],
+        bump = bounty.bump
+    )]
+    pub bounty: Account<'info, Bounty>,
+    #[account(
+        mut,
+        seeds = [b"bounty_token", bounty.key().as_ref()],
+        bump
+    )]
+    pub bounty_token_account: Account<'info, TokenAccount>,
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = Vault::SPACE,
+        seeds = [b"vault", recipient_hashed_user_id.as_ref()],
+        bump
+    )]
+    pub recipient_vault: Account<'info, Vault>,
+    #[account(
+        mut,
+        constraint = recipient_vault_token_account.owner == recipient_vault.key() @ TipError::InvalidVaultAuthority,
+        constraint = recipient_vault_token_account.mint == config.token_mint @ TipError::InvalidTokenMint
+    )]
+    pub recipient_vault_token_account: Account<'info, TokenAccount>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct CreateInvoiceArgs {
+    pub invoice_id: [u8; 32],
+    pub payer_hashed_user_id: [u8; 32],
+    pub amount: u64,
+    pub expires_at: i64,
+    pub reference: [u8; 32],
+}
+
+#[derive(Accounts)]
+pub struct RegisterMerchant<'info> {
+    #[account(
+        seeds = [b"config"],
+        bump,
+        has_one = upgrade_authority @ TipError::InvalidAuthority
+    )]
+    pub config: Account<'info, Config>,
+    #[account(mut)]
+    pub upgrade_authority: Signer<'info>,
+    /// CHECK: Any key may be registered as a merchant authority
+    pub merchant_authority: UncheckedAccount<'info>,
+    #[account(
+        init,
+        payer = upgrade_authority,
+        space = Merchant::SPACE,
+        seeds = [b"merchant", merchant_authority.key().as_ref()],
+        bump
+    )]
+    pub merchant: Account<'info, Merchant>,
+    #[account(constraint = payout_token_account.mint == config.token_mint @ TipError::InvalidTokenMint)]
+    pub payout_token_account: Account<'info, TokenAccount>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateMerchant<'info> {
+    #[account(
+        seeds = [b"config"],
+        bump,
+        has_one = upgrade_authority @ TipError::InvalidAuthority
+    )]
+    pub config: Account<'info, Config>,
+    pub upgrade_authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"merchant", merchant.authority.as_ref()],
+        bump = merchant.bump
+    )]
+    pub merchant: Account<'info, Merchant>,
+    #[account(constraint = payout_token_account.mint == config.token_mint @ TipError::InvalidTokenMint)]
+    pub payout_token_account: Account<'info, TokenAccount>,
+}
+
+#[derive(Accounts)]
+pub struct RemoveMerchant<'info> {
+    #[account(
+        seeds = [b"config"],
+        bump,
+        has_one = upgrade_authority @ TipError::InvalidAuthority
+    )]
+    pub config: Account<'info, Config>,
+    #[account(mut)]
+    pub upgrade_authority: Signer<'info>,
+    #[account(
+        mut,
+        close = upgrade_authority,
+        seeds = [b"merchant", merchant.authority.as_ref()],
+        bump = merchant.bump
+    )]
+    pub merchant: Account<'info, Merchant>,
+}
+
+#[derive(Accounts)]
+#[instruction(args: CreateInvoiceArgs)]
+pub struct CreateInvoice<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        seeds = [b"vault", recipient_vault.hashed_user_id.as_ref()],
+        bump,
+        has_one = authority
+    )]
+    pub recipient_vault: Account<'info, Vault>,
+    #[account(
+        init,
+        payer = authority,
+        space = Invoice::SPACE,
+        seeds = [b"invoice", authority.key().as_ref(), args.invoice_id.as_ref()],
+        bump
+    )]
+    pub invoice: Account<'info, Invoice>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(args: CreateInvoiceArgs)]
+pub struct CreateMerchantInvoice<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        seeds = [b"merchant", authority.key().as_ref()],
+        bump = merchant.bump,
+        has_one = authority @ TipError::InvalidMerchant
+    )]
+    pub merchant: Account<'info, Merchant>,
+    #[account(
+        init,
+        payer = authority,
+        space = Invoice::SPACE,
+        seeds = [b"invoice", authority.key().as_ref(), args.invoice_id.as_ref()],
+        bump
+    )]
+    pub invoice: Account<'info, Invoice>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CancelInvoice<'info> {
+    #[account(mut)]
+    pub creator: Signer<'info>,
+    #[account(
+        mut,
+        close = creator,
+        seeds = [b"invoice", invoice.creator.as_ref(), invoice.invoice_id.as_ref()],
+        bump = invoice.bump,
+        has_one = creator @ TipError::InvalidAuthority
+    )]
+    pub invoice: Account<'info, Invoice>,
+}
+
+#[derive(Accounts)]
+pub struct CloseInvoice<'info> {
+    /// CHECK: Receives the invoice's rent; must be the invoice creator
+    #[account(mut, address = invoice.creator @ TipError::InvalidAuthority)]
+    pub creator: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        close = creator,
+        seeds = [b"invoice", invoice.creator.as_ref(), invoice.invoice_id.as_ref()],
+        bump = invoice.bump
+    )]
+    pub invoice: Account<'info, Invoice>,
+    /// CHECK: Only inspected to see whether the invoice's merchant has been removed
+    #[account(address = invoice.merchant @ TipError::InvalidMerchant)]
+    pub merchant: Option<UncheckedAccount<'info>>,
+}
+
+#[derive(Accounts)]
+pub struct PayInvoice<'info> {
+    #[account(seeds = [b"config"], bump)]
+    pub config: Account<'info, Config>,
+    #[account(mut)]
+    pub relayer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"vault", sender_vault.hashed_user_id.as_ref()],
+        bump
+    )]
+    pub sender_vault: Account<'info, Vault>,
+    #[account(
+        mut,
+        seeds = [
+            b"allowance",
+            sender_vault.hashed_user_id.as_ref(),
+            relayer.key().as_ref()
+        ],
+        bump
+    )]
+    pub sender_allowance: Account<'info, Allowance>,
+    #[account(
+        seeds = [b"recipient_rules", sender_allowance.key().as_ref()],
+        bump = recipient_rules.bump
+    )]
+    pub recipient_rules: Option<Account<'info, RecipientRules>>,
+    #[account(
+        mut,
+        seeds = [b"invoice", invoice.creator.as_ref(), invoice.invoice_id.as_ref()],
+        bump = invoice.bump
+    )]
+    pub invoice: Account<'info, Invoice>,
+    #[account(
+        seeds = [b"merchant", merchant.authority.as_ref()],
+        bump = merchant.bump
+    )]
+    pub merchant: Option<Account<'info, Merchant>>,
+    #[account(
+        mut,
+        constraint = destination_token_account.mint == config.token_mint @ TipError::InvalidTokenMint
+    )]
+    pub destination_token_account: Account<'info, TokenAccount>,
+    #[account(
         init_if_needed,
         payer = relayer,
+        space = FeeVault::SPACE,
+        seeds = [b"fee_vault", config.key().as_ref()],
+        bump
+    )]
+    pub fee_vault: Account<'info, FeeVault>,
+    #[account(
+        mut,
+        constraint = sender_vault_token_account.owner == sender_vault.key() @ TipError::InvalidVaultAuthority,
+        constraint = sender_vault_token_account.mint == config.token_mint @ TipError::InvalidTokenMint
+    )]
+    pub sender_vault_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = fee_vault_token_account.owner == fee_vault.key() @ TipError::InvalidVaultAuthority,
+        constraint = fee_vault_token_account.mint == config.token_mint @ TipError::InvalidTokenMint
+    )]
+    pub fee_vault_token_account: Account<'info, TokenAccount>,
+    /// CHECK: Verified as the instructions sysvar
+    #[account(address = instructions_sysvar_id::ID)]
+    pub instructions_sysvar: Option<UncheckedAccount<'info>>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(group_id: [u8; 32])]
+pub struct CreateGroupVault<'info> {
+    #[account(mut)]
+    pub creator: Signer<'info>,
+    pub claim_authority: Signer<'info>,
+    #[account(seeds = [b"config"], bump)]
+    pub config: Account<'info, Config>,
+    #[account(
+        init,
+        payer = creator,
+        space = GroupVault::SPACE,
+        seeds = [b"group_vault", group_id.as_ref()],
+        bump
+    )]
+    pub group_vault: Account<'info, GroupVault>,
+    #[account(
+        init_if_needed,
+        payer = creator,
         space = Vault::SPACE,
-        seeds = [b"vault", recipient_hashed_user_id.as_ref()],
+        seeds = [b"vault", group_vault.key().as_ref()],
+        bump
+    )]
+    pub vault: Account<'info, Vault>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ProposeGroupAction<'info> {
+    #[account(mut)]
+    pub proposer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"group_vault", group_vault.group_id.as_ref()],
+        bump = group_vault.bump
+    )]
+    pub group_vault: Account<'info, GroupVault>,
+    #[account(
+        init,
+        payer = proposer,
+        space = GroupProposal::SPACE,
+        seeds = [
+            b"group_proposal",
+            group_vault.key().as_ref(),
+            group_vault.proposal_count.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub proposal: Account<'info, GroupProposal>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ApproveGroupProposal<'info> {
+    #[account(mut)]
+    pub member: Signer<'info>,
+    #[account(
+        seeds = [b"group_vault", group_vault.group_id.as_ref()],
+        bump = group_vault.bump
+    )]
+    pub group_vault: Account<'info, GroupVault>,
+    #[account(
+        mut,
+        seeds = [
+            b"group_proposal",
+            group_vault.key().as_ref(),
+            proposal.proposal_id.to_le_bytes().as_ref()
+        ],
+        bump = proposal.bump,
+        has_one = group_vault @ TipError::InvalidGroupProposal
+    )]
+    pub proposal: Account<'info, GroupProposal>,
+    #[account(
+        init,
+        payer = member,
+        space = GroupApproval::SPACE,
+        seeds = [b"group_approval", proposal.key().as_ref(), member.key().as_ref()],
+        bump
+    )]
+    pub approval: Account<'info, GroupApproval>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteGroupProposal<'info> {
+    #[account(seeds = [b"config"], bump)]
+    pub config: Account<'info, Config>,
+    pub member: Signer<'info>,
+    /// CHECK: Receives the proposal's rent; must be the proposer
+    #[account(mut, address = proposal.proposer @ TipError::InvalidGroupProposal)]
+    pub proposer: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [b"group_vault", group_vault.group_id.as_ref()],
+        bump = group_vault.bump
+    )]
+    pub group_vault: Account<'info, GroupVault>,
+    #[account(
+        mut,
+        close = proposer,
+        seeds = [
+            b"group_proposal",
+            group_vault.key().as_ref(),
+            proposal.proposal_id.to_le_bytes().as_ref()
+        ],
+        bump = proposal.bump,
+        has_one = group_vault @ TipError::InvalidGroupProposal
+    )]
+    pub proposal: Account<'info, GroupProposal>,
+    #[account(
+        mut,
+        seeds = [b"vault", group_vault.key().as_ref()],
+        bump,
+        address = group_vault.vault @ TipError::InvalidGroupProposal
+    )]
+    pub vault: Account<'info, Vault>,
+    #[account(
+        mut,
+        constraint = vault_token_account.owner == vault.key() @ TipError::InvalidVaultAuthority,
+        constraint = vault_token_account.mint == vault.token_mint @ TipError::InvalidTokenMint
+    )]
+    pub vault_token_account: Option<Account<'info, TokenAccount>>,
+    #[account(
+        mut,
+        constraint = destination_token_account.mint == vault.token_mint @ TipError::InvalidTokenMint
+    )]
+    pub destination_token_account: Option<Account<'info, TokenAccount>>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct CancelGroupProposal<'info> {
+    #[account(mut)]
+    pub proposer: Signer<'info>,
+    #[account(
+        mut,
+        close = proposer,
+        seeds = [
+            b"group_proposal",
+            proposal.group_vault.as_ref(),
+            proposal.proposal_id.to_le_bytes().as_ref()
+        ],
+        bump = proposal.bump,
+        has_one = proposer @ TipError::InvalidAuthority
+    )]
+    pub proposal: Account<'info, GroupProposal>,
+}
+
+#[derive(Accounts)]
+pub struct CloseGroupApproval<'info> {
+    #[account(mut)]
+    pub member: Signer<'info>,
+    /// CHECK: Must be the approval's proposal, which has already been closed
+    #[account(address = approval.proposal @ TipError::InvalidGroupProposal)]
+    pub proposal: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        close = member,
+        seeds = [b"group_approval", approval.proposal.as_ref(), member.key().as_ref()],
+        bump = approval.bump,
+        has_one = member @ TipError::InvalidAuthority
+    )]
+    pub approval: Account<'info, GroupApproval>,
+}
+
+#[derive(Accounts)]
+pub struct CloseTipReceipt<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        close = payer,
+        seeds = [
+            b"tip_receipt",
+            tip_receipt.sender_vault.as_ref(),
+            tip_receipt.tip_id.as_ref()
+        ],
+        bump = tip_receipt.bump,
+        has_one = payer @ TipError::InvalidTipReceipt
+    )]
+    pub tip_receipt: Account<'info, TipReceipt>,
+}
+
+#[derive(Accounts)]
+pub struct Withdraw<'info> {
+    #[account(seeds = [b"config"], bump)]
+    pub config: Account<'info, Config>,
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"vault", vault.hashed_user_id.as_ref()],
+        bump,
+        has_one = authority
+    )]
+    pub vault: Account<'info, Vault>,
+    #[account(
+        mut,
+        constraint = vault_token_account.owner == vault.key() @ TipError::InvalidVaultAuthority,
+        constraint = vault_token_account.mint == config.token_mint @ TipError::InvalidTokenMint
+    )]
+    pub vault_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = destination_token_account.mint == config.token_mint @ TipError::InvalidTokenMint
+    )]
+    pub destination_token_account: Account<'info, TokenAccount>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawToWallet<'info> {
+    #[account(seeds = [b"config"], bump)]
+    pub config: Account<'info, Config>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"vault", vault.hashed_user_id.as_ref()],
+        bump,
+        has_one = authority
+    )]
+    pub vault: Account<'info, Vault>,
+    #[account(
+        mut,
+        constraint = vault_token_account.owner == vault.key() @ TipError::InvalidVaultAuthority,
+        constraint = vault_token_account.mint == config.token_mint @ TipError::InvalidTokenMint
+    )]
+    pub vault_token_account: Account<'info, TokenAccount>,
+    /// CHECK: Any wallet; only used to derive the destination associated token account
+    pub destination: UncheckedAccount<'info>,
+    #[account(
+        init_if_needed,
+        payer = authority,
+        associated_token::mint = token_mint,
+        associated_token::authority = destination
+    )]
+    pub destination_token_account: Account<'info, TokenAccount>,
+    #[account(constraint = token_mint.key() == config.token_mint @ TipError::InvalidTokenMint)]
+    pub token_mint: Account<'info, Mint>,
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RelayedWithdraw<'info> {
+    #[account(seeds = [b"config"], bump)]
+    pub config: Account<'info, Config>,
+    #[account(
+        mut,
+        constraint = relayer.key() == config.relayer @ TipError::InvalidRelayer
+    )]
+    pub relayer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"vault", vault.hashed_user_id.as_ref()],
+        bump,
+        constraint = vault.claimed @ TipError::InvalidVaultAuthority,
+        constraint = vault.token_mint == config.token_mint @ TipError::InvalidTokenMint
+    )]
+    pub vault: Account<'info, Vault>,
+    #[account(
+        mut,
+        constraint = vault_token_account.owner == vault.key() @ TipError::InvalidVaultAuthority,
+        constraint = vault_token_account.mint == config.token_mint @ TipError::InvalidTokenMint
+    )]
+    pub vault_token_account: Account<'info, TokenAccount>,
+    /// CHECK: Must match the destination signed in the withdraw intent
+    pub destination: UncheckedAccount<'info>,
+    #[account(
+        init_if_needed,
+        payer = relayer,
+        associated_token::mint = token_mint,
+        associated_token::authority = destination
+    )]
+    pub destination_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        token::authority = relayer,
+        constraint = relayer_token_account.mint == config.token_mint @ TipError::InvalidTokenMint
+    )]
+    pub relayer_token_account: Account<'info, TokenAccount>,
+    #[account(constraint = token_mint.key() == config.token_mint @ TipError::InvalidTokenMint)]
+    pub token_mint: Account<'info, Mint>,
+    /// CHECK: Verified as the instructions sysvar
+    #[account(address = instructions_sysvar_id::ID)]
+    pub instructions_sysvar: AccountInfo<'info>,
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(hashed_user_id: [u8; 32])]
+pub struct MigrateVault<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    /// CHECK: Legacy vault PDA; owner and discriminator are verified before resizing
+    #[account(
+        mut,
+        seeds = [b"vault", hashed_user_id.as_ref()],
+        bump,
+        owner = crate::ID @ TipError::InvalidMigrationAccount
+    )]
+    pub vault: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateConfig<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    /// CHECK: Legacy config PDA; owner and discriminator are verified before resizing
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump,
+        owner = crate::ID @ TipError::InvalidMigrationAccount
+    )]
+    pub config: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetWithdrawLimit<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"vault", vault.hashed_user_id.as_ref()],
+        bump,
+        has_one = authority
+    )]
+    pub vault: Account<'info, Vault>,
+}
+
+#[derive(Accounts)]
+pub struct SetTipSignatureRequired<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"vault", vault.hashed_user_id.as_ref()],
+        bump,
+        has_one = authority
+    )]
+    pub vault: Account<'info, Vault>,
+}
+
+#[derive(Accounts)]
+pub struct QueueWithdrawal<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"vault", vault.hashed_user_id.as_ref()],
+        bump,
+        has_one = authority
+    )]
+    pub vault: Account<'info, Vault>,
+    /// CHECK: Any wallet; recorded as the payout owner for the queued withdrawal
+    pub destination: UncheckedAccount<'info>,
+    #[account(
+        init,
+        payer = authority,
+        space = PendingWithdrawal::SPACE,
+        seeds = [
+            b"pending_withdrawal",
+            vault.key().as_ref(),
+            vault.pending_withdrawal_count.to_le_bytes().as_ref()
+        ],
         bump
     )]
-    pub recipient_vault: Account<'info, Vault>,
+    pub pending_withdrawal: Account<'info, PendingWithdrawal>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteWithdrawal<'info> {
+    #[account(seeds = [b"config"], bump)]
+    pub config: Account<'info, Config>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"vault", vault.hashed_user_id.as_ref()],
+        bump,
+        has_one = authority,
+        constraint = vault.token_mint == config.token_mint @ TipError::InvalidTokenMint
+    )]
+    pub vault: Account<'info, Vault>,
+    #[account(
+        mut,
+        close = authority,
+        seeds = [
+            b"pending_withdrawal",
+            vault.key().as_ref(),
+            pending_withdrawal.id.to_le_bytes().as_ref()
+        ],
+        bump = pending_withdrawal.bump,
+        has_one = vault @ TipError::InvalidPendingWithdrawal,
+        has_one = authority @ TipError::InvalidVaultAuthority,
+        has_one = destination @ TipError::InvalidPendingWithdrawal
+    )]
+    pub pending_withdrawal: Account<'info, PendingWithdrawal>,
+    #[account(
+        mut,
+        constraint = vault_token_account.owner == vault.key() @ TipError::InvalidVaultAuthority,
+        constraint = vault_token_account.mint == config.token_mint @ TipError::InvalidTokenMint
+    )]
+    pub vault_token_account: Account<'info, TokenAccount>,
+    /// CHECK: Must match the destination recorded on the pending withdrawal
+    pub destination: UncheckedAccount<'info>,
     #[account(
         init_if_needed,
-        payer = relayer,
-        space = FeeVault::SPACE,
-        seeds = [b"fee_vault", config.key().as_ref()],
-        bump
+        payer = authority,
+        associated_token::mint = token_mint,
+        associated_token::authority = destination
     )]
-    pub fee_vault: Account<'info, FeeVault>,
+    pub destination_token_account: Account<'info, TokenAccount>,
+    #[account(constraint = token_mint.key() == config.token_mint @ TipError::InvalidTokenMint)]
+    pub token_mint: Account<'info, Mint>,
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CancelWithdrawal<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        seeds = [b"vault", vault.hashed_user_id.as_ref()],
+        bump,
+        has_one = authority
+    )]
+    pub vault: Account<'info, Vault>,
     #[account(
         mut,
-        constraint = sender_vault_token_account.owner == sender_vault.key() @ TipError::InvalidVaultAuthority,
-        constraint = sender_vault_token_account.mint == config.token_mint @ TipError::InvalidTokenMint
+        close = authority,
+        seeds = [
+            b"pending_withdrawal",
+            vault.key().as_ref(),
+            pending_withdrawal.id.to_le_bytes().as_ref()
+        ],
+        bump = pending_withdrawal.bump,
+        has_one = vault @ TipError::InvalidPendingWithdrawal,
+        has_one = authority @ TipError::InvalidVaultAuthority
     )]
-    pub sender_vault_token_account: Account<'info, TokenAccount>,
+    pub pending_withdrawal: Account<'info, PendingWithdrawal>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawFee<'info> {
     #[account(
         mut,
-        constraint = recipient_vault_token_account.owner == recipient_vault.key() @ TipError::InvalidVaultAuthority,
-        constraint = recipient_vault_token_account.mint == config.token_mint @ TipError::InvalidTokenMint
+        seeds = [b"config"],
+        bump,
+        has_one = upgrade_authority @ TipError::InvalidAuthority
     )]
-    pub recipient_vault_token_account: Account<'info, TokenAccount>,
+    pub config: Account<'info, Config>,
+    pub upgrade_authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"fee_vault", config.key().as_ref()],
+        bump = fee_vault.bump,
+        constraint = fee_vault.config == config.key() @ TipError::InvalidFeeVault,
+        constraint = fee_vault.token_mint == config.token_mint @ TipError::InvalidTokenMint
+    )]
+    pub fee_vault: Account<'info, FeeVault>,
     #[account(
         mut,
         constraint = fee_vault_token_account.owner == fee_vault.key() @ TipError::InvalidVaultAuthority,
         constraint = fee_vault_token_account.mint == config.token_mint @ TipError::InvalidTokenMint
     )]
     pub fee_vault_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = destination_token_account.mint == config.token_mint @ TipError::InvalidTokenMint
+    )]
+    pub destination_token_account: Account<'info, TokenAccount>,
     pub token_program: Program<'info, Token>,
-    pub system_program: Program<'info, System>,
-    pub rent: Sysvar<'info, Rent>,
 }
 
-#[derive(Accounts)]
-pub struct Withdraw<'info> {
-    #[account(seeds = [b"config"], bump)]
-    pub config: Account<'info, Config>,
-    pub authority: Signer<'info>,
-    #[account(
-        mut,
-        seeds = [b"vault", vault.hashed_user_id.as_ref()],
-        bump,
-        has_one = authority
-    )]
-    pub vault: Account<'info, Vault>,
-    #[account(
-        mut,
-        constraint = vault_token_account.owner == vault.key() @ TipError::InvalidVaultAuthority,
-        constraint = vault_token_account.mint == config.token_mint @ TipError::InvalidTokenMint
-    )]
-    pub vault_token_account: Account<'info, TokenAccount>,
-    #[account(
-        mut,
-        constraint = destination_token_account.mint == config.token_mint @ TipError::InvalidTokenMint
-    )]
-    pub destination_token_account: Account<'info, TokenAccount>,
-    pub token_program: Program<'info, Token>,
+#[derive(Accounts)]
+pub struct SetRelayer<'info> {
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump,
+        has_one = upgrade_authority @ TipError::InvalidAuthority
+    )]
+    pub config: Account<'info, Config>,
+    pub upgrade_authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetFeeRate<'info> {
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump,
+        has_one = upgrade_authority @ TipError::InvalidAuthority
+    )]
+    pub config: Account<'info, Config>,
+    pub upgrade_authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetRateLimit<'info> {
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump,
+        has_one = upgrade_authority @ TipError::InvalidAuthority
+    )]
+    pub config: Account<'info, Config>,
+    pub upgrade_authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetVaultRateLimit<'info> {
+    #[account(
+        seeds = [b"config"],
+        bump,
+        has_one = upgrade_authority @ TipError::InvalidAuthority
+    )]
+    pub config: Account<'info, Config>,
+    pub upgrade_authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"vault", vault.hashed_user_id.as_ref()],
+        bump
+    )]
+    pub vault: Account<'info, Vault>,
+}
+
+#[derive(Accounts)]
+pub struct SetMinTipAmounts<'info> {
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump,
+        has_one = upgrade_authority @ TipError::InvalidAuthority
+    )]
+    pub config: Account<'info, Config>,
+    pub upgrade_authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetTipDisputeWindow<'info> {
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump,
+        has_one = upgrade_authority @ TipError::InvalidAuthority
+    )]
+    pub config: Account<'info, Config>,
+    pub upgrade_authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetClaimAuthority<'info> {
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump,
+        has_one = upgrade_authority @ TipError::InvalidAuthority
+    )]
+    pub config: Account<'info, Config>,
+    pub upgrade_authority: Signer<'info>,
+}
+
+#[account]
+pub struct Config {
+    pub upgrade_authority: Pubkey,
+    pub relayer: Pubkey,
+    pub token_mint: Pubkey,
+    pub fee_bps: u16,
+    pub claim_authority: Pubkey,
+    pub rate_limit_max_tips: u64,
+    pub rate_limit_window_seconds: i64,
+    pub min_tip_amount: u64,
+    pub min_new_vault_tip_amount: u64,
+    pub tip_dispute_window_seconds: i64,
+    pub previous_relayer: Pubkey,
+}
+
+impl Config {
+    pub const SPACE: usize = 8 + 32 + 32 + 32 + 2 + 32 + 8 + 8 + 8 + 8 + 8 + 32;
+}
+
+#[account]
+#[derive(Default)]
+pub struct Vault {
+    pub authority: Pubkey,
+    pub hashed_user_id: [u8; 32],
+    pub token_mint: Pubkey,
+    pub claimed: bool,
+    pub daily_withdraw_limit: u64,
+    pub withdrawn_in_window: u64,
+    pub withdraw_window_start: i64,
+    pub pending_withdraw_limit: u64,
+    pub pending_withdraw_limit_at: i64,
+    pub pending_withdrawal_count: u64,
+    pub withdraw_nonce: u64,
+    pub require_tip_signature: bool,
+    pub tip_bucket_tokens: u64,
+    pub tip_bucket_updated_at: i64,
+    pub rate_limit_override: bool,
+    pub rate_limit_max_tips: u64,
+    pub rate_limit_window_seconds: i64,
+}
+
+impl Vault {
+    pub const SPACE: usize =
+        8 + 32 + 32 + 32 + 1 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 1 + 8 + 8 + 1 + 8 + 8;
+}
+
+#[account]
+pub struct PendingWithdrawal {
+    pub vault: Pubkey,
+    pub authority: Pubkey,
+    pub destination: Pubkey,
+    pub amount: u64,
+    pub executable_at: i64,
+    pub id: u64,
+    pub bump: u8,
+}
+
+impl PendingWithdrawal {
+    pub const SPACE: usize = 8 + 32 + 32 + 32 + 8 + 8 + 8 + 1;
+}
+
+#[account]
+pub struct FeeVault {
+    pub config: Pubkey,
+    pub token_mint: Pubkey,
+    pub bump: u8,
+}
+
+impl FeeVault {
+    pub const SPACE: usize = 8 + 32 + 32 + 1;
+}
+
+#[account]
+pub struct Allowance {
+    pub authority: Pubkey,
+    pub hashed_user_id: [u8; 32],
+    pub spender: Pubkey,
+    pub cap: u64,
+    pub remaining: u64,
+    pub period_seconds: i64,
+    pub period_start: i64,
+    pub expires_at: i64,
+    pub max_per_tip: u64,
+    pub has_recipient_rules: bool,
+    pub sender_nonces: NonceWindow,
+}
+
+impl Allowance {
+    pub const SPACE: usize = 8 + 32 + 32 + 32 + 8 + 8 + 8 + 8 + 8 + 8 + 1 + NonceWindow::SPACE;
+}
+
+#[account(discriminator = Allowance::DISCRIMINATOR)]
+pub struct LegacyAllowance {
+    pub authority: Pubkey,
+    pub hashed_user_id: [u8; 32],
+    pub cap: u64,
+    pub remaining: u64,
+}
+
+impl LegacyAllowance {
+    pub const SPACE: usize = 8 + 32 + 32 + 8 + 8;
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct NonceWindow {
+    pub highest: u64,
+    pub bitmap: u64,
+}
+
+impl NonceWindow {
+    pub const SIZE: u64 = 64;
+    pub const SPACE: usize = 8 + 8;
+}
+
+#[account]
+pub struct TipEscrow {
+    pub config: Pubkey,
+    pub token_mint: Pubkey,
+    pub bump: u8,
+}
+
+impl TipEscrow {
+    pub const SPACE: usize = 8 + 32 + 32 + 1;
+}
+
+#[account]
+pub struct PendingTip {
+    pub tip_id: [u8; 32],
+    pub relayer: Pubkey,
+    pub sender_vault: Pubkey,
+    pub sender_allowance: Pubkey,
+    pub recipient_vault: Pubkey,
+    pub sender_hashed_user_id: [u8; 32],
+    pub recipient_hashed_user_id: [u8; 32],
+    pub amount: u64,
+    pub fee_amount: u64,
+    pub created_at: i64,
+    pub finalizable_at: i64,
+    pub bump: u8,
+}
+
+impl PendingTip {
+    pub const SPACE: usize = 8 + 32 + 32 + 32 + 32 + 32 + 32 + 32 + 8 + 8 + 8 + 8 + 1;
+}
+
+#[account]
+pub struct TipReceipt {
+    pub tip_id: [u8; 32],
+    pub sender_vault: Pubkey,
+    pub payer: Pubkey,
+    pub created_at: i64,
+    pub bump: u8,
+}
+
+impl TipReceipt {
+    pub const SPACE: usize = 8 + 32 + 32 + 32 + 8 + 1;
+}
+
+#[account]
+pub struct Session {
+    pub vault: Pubkey,
+    pub authority: Pubkey,
+    pub session_key: Pubkey,
+    pub cap: u64,
+    pub remaining: u64,
+    pub expires_at: i64,
+    pub bump: u8,
+    pub sender_nonces: NonceWindow,
+}
+
+impl Session {
+    pub const SPACE: usize = 8 + 32 + 32 + 32 + 8 + 8 + 8 + 1 + NonceWindow::SPACE;
+}
+
+#[account]
+pub struct Subscription {
+    pub sender_vault: Pubkey,
+    pub authority: Pubkey,
+    pub sender_hashed_user_id: [u8; 32],
+    pub recipient_hashed_user_id: [u8; 32],
+    pub amount: u64,
+    pub interval_seconds: i64,
+    pub next_due: i64,
+    pub remaining_periods: u64,
+    pub bump: u8,
+}
+
+impl Subscription {
+    pub const SPACE: usize = 8 + 32 + 32 + 32 + 32 + 8 + 8 + 8 + 8 + 1;
+}
+
+#[account]
+pub struct Pool {
+    pub pool_id: [u8; 32],
+    pub creator: Pubkey,
+    pub recipient_hashed_user_id: [u8; 32],
+    pub token_mint: Pubkey,
+    pub target_amount: u64,
+    pub deadline: i64,
+    pub total_contributed: u64,
+    pub total_fees: u64,
+    pub contribution_count: u64,
+    pub claimed: bool,
+    pub bump: u8,
+}
+
+impl Pool {
+    pub const SPACE: usize = 8 + 32 + 32 + 32 + 32 + 8 + 8 + 8 + 8 + 8 + 1 + 1;
+}
+
+#[account]
+pub struct Bounty {
+    pub bounty_id: [u8; 32],
+    pub creator: Pubkey,
+    pub creator_vault: Pubkey,
+    pub creator_hashed_user_id: [u8; 32],
+    pub amount: u64,
+    pub created_at: i64,
+    pub bump: u8,
+}
+
+impl Bounty {
+    pub const SPACE: usize = 8 + 32 + 32 + 32 + 32 + 8 + 8 + 1;
+}
+
+#[account]
+pub struct Merchant {
+    pub authority: Pubkey,
+    pub payout_token_account: Pubkey,
+    pub bump: u8,
+}
+
+impl Merchant {
+    pub const SPACE: usize = 8 + 32 + 32 + 1;
+}
+
+#[account]
+pub struct Invoice {
+    pub invoice_id: [u8; 32],
+    pub creator: Pubkey,
+    pub recipient_hashed_user_id: [u8; 32],
+    pub merchant: Pubkey,
+    pub payer_hashed_user_id: [u8; 32],
+    pub amount: u64,
+    pub expires_at: i64,
+    pub reference: [u8; 32],
+    pub paid: bool,
+    pub paid_at: i64,
+    pub bump: u8,
+}
+
+impl Invoice {
+    pub const SPACE: usize = 8 + 32 + 32 + 32 + 32 + 32 + 8 + 8 + 32 + 1 + 8 + 1;
+}
+
+#[account]
+pub struct GroupVault {
+    pub group_id: [u8; 32],
+    pub vault: Pubkey,
+    pub members: Vec<Pubkey>,
+    pub threshold: u8,
+    pub version: u64,
+    pub proposal_count: u64,
+    pub bump: u8,
+}
+
+impl GroupVault {
+    pub const MAX_MEMBERS: usize = 10;
+    pub const SPACE: usize = 8 + 32 + 32 + 4 + 32 * Self::MAX_MEMBERS + 1 + 8 + 8 + 1;
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq)]
+pub enum GroupProposalAction {
+    Withdraw { destination: Pubkey, amount: u64 },
+    AddMember { member: Pubkey, threshold: u8 },
+    RemoveMember { member: Pubkey, threshold: u8 },
+    SetWithdrawLimit { daily_limit: u64 },
+}
+
+impl GroupProposalAction {
+    pub const SPACE: usize = 1 + 32 + 8;
+}
+
+#[account]
+pub struct GroupProposal {
+    pub group_vault: Pubkey,
+    pub proposal_id: u64,
+    pub proposer: Pubkey,
+    pub action: GroupProposalAction,
+    pub version: u64,
+    pub approvals: u8,
+    pub created_at: i64,
+    pub bump: u8,
+}
+
+impl GroupProposal {
+    pub const SPACE: usize = 8 + 32 + 8 + 32 + GroupProposalAction::SPACE + 8 + 1 + 8 + 1;
+}
+
+#[account]
+pub struct GroupApproval {
+    pub proposal: Pubkey,
+    pub member: Pubkey,
+    pub bump: u8,
+}
+
+impl GroupApproval {
+    pub const SPACE: usize = 8 + 32 + 32 + 1;
+}
+
+#[account]
+pub struct PoolContribution {
+    pub pool: Pubkey,
+    pub sender_vault: Pubkey,
+    pub payer: Pubkey,
+    pub amount: u64,
+    pub fee_amount: u64,
+    pub bump: u8,
+}
+
+impl PoolContribution {
+    pub const SPACE: usize = 8 + 32 + 32 + 32 + 8 + 8 + 1;
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub enum RecipientRuleMode {
+    Allowlist,
+    Blocklist,
+}
+
+#[account]
+pub struct RecipientRules {
+    pub allowance: Pubkey,
+    pub mode: RecipientRuleMode,
+    pub recipients: Vec<[u8; 32]>,
+    pub bump: u8,
+}
+
+impl RecipientRules {
+    pub const MAX_RECIPIENTS: usize = 20;
+    pub const SPACE: usize = 8 + 32 + 1 + 4 + 32 * Self::MAX_RECIPIENTS + 1;
+}
+
+fn calculate_fee(amount: u64, fee_bps: u16) -> Result<u64> {
+    if fee_bps == 0 {
+        return Ok(0);
+    }
+
+    let numerator = (amount as u128)
+        .checked_mul(fee_bps as u128)
+        .ok_or(TipError::FeeCalculationOverflow)?;
+    let mut fee = numerator / 10_000;
+
+    if numerator % 10_000 != 0 {
+        fee = fee.checked_add(1).ok_or(TipError::FeeCalculationOverflow)?;
+    }
+
+    if fee > u64::MAX as u128 {
+        return Err(TipError::FeeCalculationOverflow.into());
+    }
+
+    Ok(fee as u64)
+}
+
+fn recipient_permitted(recipient_rules: &RecipientRules, recipient_hash: &[u8; 32]) -> bool {
+    let listed = recipient_rules.recipients.contains(recipient_hash);
+
+    match recipient_rules.mode {
+        RecipientRuleMode::Allowlist => listed,
+        RecipientRuleMode::Blocklist => !listed,
+    }
+}
+
+fn check_allowance_recipient(
+    allowance: &Allowance,
+    recipient_rules: Option<&RecipientRules>,
+    recipient_hash: &[u8; 32],
+    amount: u64,
+) -> Result<()> {
+    require!(
+        allowance.max_per_tip == 0 || amount <= allowance.max_per_tip,
+        TipError::TipExceedsPerTipLimit
+    );
+
+    if allowance.has_recipient_rules {
+        let recipient_rules = recipient_rules.ok_or(TipError::MissingRecipientRules)?;
+        require!(
+            recipient_permitted(recipient_rules, recipient_hash),
+            TipError::RecipientNotAllowed
+        );
+    }
+
+    Ok(())
+}
+
+fn check_wallet_recipient(allowance: &Allowance, amount: u64) -> Result<()> {
+    require!(
+        allowance.max_per_tip == 0 || amount <= allowance.max_per_tip,
+        TipError::TipExceedsPerTipLimit
+    );
+    require!(
+        !allowance.has_recipient_rules,
+        TipError::WalletRecipientRestricted
+    );
+
+    Ok(())
+}
+
+fn spend_allowance(
+    allowance: &mut Allowance,
+    vault: &mut Account<Vault>,
+    vault_bump: u8,
+    sender_nonce: u64,
+    total_amount: u64,
+    now: i64,
+) -> Result<()> {
+    require!(
+        allowance.expires_at == 0 || now < allowance.expires_at,
+        TipError::AllowanceExpired
+    );
+
+    consume_sender_nonce(&mut allowance.sender_nonces, sender_nonce)?;
+
+    if refresh_allowance_window(allowance, now) {
+        emit_allowance_updated(allowance, vault.key(), vault_bump);
+    }
+
+    allowance.remaining = allowance
+        .remaining
+        .checked_sub(total_amount)
+        .ok_or(TipError::AllowanceExceeded)?;
+
+    consume_withdraw_limit(vault, total_amount, now)
+}
+
+fn emit_allowance_updated(allowance: &Allowance, vault: Pubkey, vault_bump: u8) {
+    emit!(AllowanceUpdated {
+        authority: allowance.authority,
+        spender: allowance.spender,
+        vault,
+        vault_bump,
+        hashed_user_id: allowance.hashed_user_id,
+        cap: allowance.cap,
+        remaining: allowance.remaining,
+        period_seconds: allowance.period_seconds,
+        period_start: allowance.period_start,
+        expires_at: allowance.expires_at,
+        max_per_tip: allowance.max_per_tip,
+    });
+}
+
+fn refresh_allowance_window(allowance: &mut Allowance, now: i64) -> bool {
+    if allowance.period_seconds <= 0 {
+        return false;
+    }
+
+    let elapsed = now.saturating_sub(allowance.period_start);
+    if elapsed < allowance.period_seconds {
+        return false;
+    }
+
+    let periods = elapsed / allowance.period_seconds;
+    allowance.period_start = allowance
+        .period_start
+        .saturating_add(periods.saturating_mul(allowance.period_seconds));
+    allowance.remaining = allowance.cap;
+
+    true
+}
+
+fn withdraw_from_vault<'info>(
+    config: &Config,
+    vault: &mut Account<'info, Vault>,
+    vault_token_account: &Account<'info, TokenAccount>,
+    destination_token_account: &Account<'info, TokenAccount>,
+    token_program: AccountInfo<'info>,
+    vault_bump: u8,
+    amount: u64,
+) -> Result<()> {
+    require_keys_eq!(
+        vault.token_mint,
+        config.token_mint,
+        TipError::InvalidTokenMint
+    );
+
+    require!(
+        vault_token_account.amount >= amount,
+        TipError::InsufficientVaultBalance
+    );
+
+    let now = Clock::get()?.unix_timestamp;
+    consume_withdraw_limit(vault, amount, now)?;
+
+    transfer_from_vault(
+        token_program,
+        vault_token_account.to_account_info(),
+        destination_token_account.to_account_info(),
+        vault.to_account_info(),
+        &vault.hashed_user_id,
+        vault_bump,
+        amount,
+    )?;
+
+    emit!(WithdrawEvent {
+        authority: vault.authority,
+        vault: vault.key(),
+        vault_bump,
+        hashed_user_id: vault.hashed_user_id,
+        destination: destination_token_account.owner,
+        destination_token_account: destination_token_account.key(),
+        amount,
+    });
+
+    Ok(())
+}
+
+fn transfer_from_vault<'info>(
+    token_program: AccountInfo<'info>,
+    from: AccountInfo<'info>,
+    to: AccountInfo<'info>,
+    vault: AccountInfo<'info>,
+    hashed_user_id: &[u8; 32],
+    vault_bump: u8,
+    amount: u64,
+) -> Result<()> {
+    let vault_seeds: [&[u8]; 3] = [b"vault", hashed_user_id.as_ref(), &[vault_bump]];
+    let signer_seeds: [&[&[u8]]; 1] = [&vault_seeds];
+
+    let cpi_accounts = Transfer {
+        from,
+        to,
+        authority: vault,
+    };
+
+    let cpi_ctx = CpiContext::new_with_signer(token_program, cpi_accounts, &signer_seeds);
+
+    token::transfer(cpi_ctx, amount)
+}
+
+fn transfer_from_pool<'info>(
+    token_program: AccountInfo<'info>,
+    from: AccountInfo<'info>,
+    to: AccountInfo<'info>,
+    pool: &Account<'info, Pool>,
+    amount: u64,
+) -> Result<()> {
+    let pool_seeds: [&[u8]; 4] = [
+        b"pool",
+        pool.creator.as_ref(),
+        pool.pool_id.as_ref(),
+        &[pool.bump],
+    ];
+    let signer_seeds: [&[&[u8]]; 1] = [&pool_seeds];
+
+    let cpi_accounts = Transfer {
+        from,
+        to,
+        authority: pool.to_account_info(),
+    };
+
+    let cpi_ctx = CpiContext::new_with_signer(token_program, cpi_accounts, &signer_seeds);
+
+    token::transfer(cpi_ctx, amount)
+}
+
+fn close_pool_token_account<'info>(
+    token_program: AccountInfo<'info>,
+    pool_token_account: AccountInfo<'info>,
+    rent_destination: AccountInfo<'info>,
+    pool: &Account<'info, Pool>,
+) -> Result<()> {
+    let pool_seeds: [&[u8]; 4] = [
+        b"pool",
+        pool.creator.as_ref(),
+        pool.pool_id.as_ref(),
+        &[pool.bump],
+    ];
+    let signer_seeds: [&[&[u8]]; 1] = [&pool_seeds];
+
+    let cpi_accounts = CloseAccount {
+        account: pool_token_account,
+        destination: rent_destination,
+        authority: pool.to_account_info(),
+    };
+    token::close_account(CpiContext::new_with_signer(
+        token_program,
+        cpi_accounts,
+        &signer_seeds,
+    ))
+}
+
+fn release_bounty_escrow<'info>(
+    token_program: AccountInfo<'info>,
+    bounty_token_account: AccountInfo<'info>,
+    to: AccountInfo<'info>,
+    bounty: &Account<'info, Bounty>,
+    rent_destination: AccountInfo<'info>,
+    amount: u64,
+) -> Result<()> {
+    let bounty_seeds: [&[u8]; 4] = [
+        b"bounty",
+        bounty.creator_vault.as_ref(),
+        bounty.bounty_id.as_ref(),
+        &[bounty.bump],
+    ];
+    let signer_seeds: [&[&[u8]]; 1] = [&bounty_seeds];
+
+    if amount > 0 {
+        let cpi_accounts = Transfer {
+            from: bounty_token_account.clone(),
+            to,
+            authority: bounty.to_account_info(),
+        };
+        token::transfer(
+            CpiContext::new_with_signer(token_program.clone(), cpi_accounts, &signer_seeds),
+            amount,
+        )?;
+    }
+
+    let cpi_accounts = CloseAccount {
+        account: bounty_token_account,
+        destination: rent_destination,
+        authority: bounty.to_account_info(),
+    };
+    token::close_account(CpiContext::new_with_signer(
+        token_program,
+        cpi_accounts,
+        &signer_seeds,
+    ))
+}
+
+fn init_invoice(
+    invoice: &mut Account<Invoice>,
+    args: &CreateInvoiceArgs,
+    creator: Pubkey,
+    recipient_hashed_user_id: [u8; 32],
+    merchant: Pubkey,
+    bump: u8,
+) -> Result<()> {
+    require!(args.amount > 0, TipError::InvalidTipAmount);
+    require!(
+        args.payer_hashed_user_id.iter().any(|byte| *byte != 0),
+        TipError::InvalidHashedUserId
+    );
+
+    let now = Clock::get()?.unix_timestamp;
+    require!(
+        args.expires_at == 0 || args.expires_at > now,
+        TipError::InvoiceExpired
+    );
+
+    invoice.invoice_id = args.invoice_id;
+    invoice.creator = creator;
+    invoice.recipient_hashed_user_id = recipient_hashed_user_id;
+    invoice.merchant = merchant;
+    invoice.payer_hashed_user_id = args.payer_hashed_user_id;
+    invoice.amount = args.amount;
+    invoice.expires_at = args.expires_at;
+    invoice.reference = args.reference;
+    invoice.paid = false;
+    invoice.paid_at = 0;
+    invoice.bump = bump;
+
+    emit!(InvoiceCreated {
+        invoice: invoice.key(),
+        invoice_id: args.invoice_id,
+        creator,
+        recipient_hashed_user_id,
+        merchant,
+        payer_hashed_user_id: args.payer_hashed_user_id,
+        amount: args.amount,
+        expires_at: args.expires_at,
+        reference: args.reference,
+    });
+
+    Ok(())
+}
+
+fn validate_group_members(members: &[Pubkey], threshold: u8) -> Result<()> {
+    require!(
+        !members.is_empty() && members.len() <= GroupVault::MAX_MEMBERS,
+        TipError::InvalidGroupMembers
+    );
+    require!(
+        members
+            .iter()
+            .enumerate()
+            .all(|(index, member)| *member != Pubkey::default()
+                && !members[..index].contains(member)),
+        TipError::InvalidGroupMembers
+    );
+    require!(
+        threshold > 0 && threshold as usize <= members.len(),
+        TipError::InvalidGroupThreshold
+    );
+
+    Ok(())
 }
 
-#[derive(Accounts)]
-pub struct WithdrawFee<'info> {
-    #[account(
-        mut,
-        seeds = [b"config"],
-        bump,
-        has_one = upgrade_authority @ TipError::InvalidAuthority
-    )]
-    pub config: Account<'info, Config>,
-    pub upgrade_authority: Signer<'info>,
-    #[account(
-        mut,
-        seeds = [b"fee_vault", config.key().as_ref()],
-        bump = fee_vault.bump,
-        constraint = fee_vault.config == config.key() @ TipError::InvalidFeeVault,
-        constraint = fee_vault.token_mint == config.token_mint @ TipError::InvalidTokenMint
-    )]
-    pub fee_vault: Account<'info, FeeVault>,
-    #[account(
-        mut,
-        constraint = fee_vault_token_account.owner == fee_vault.key() @ TipError::InvalidVaultAuthority,
-        constraint = fee_vault_token_account.mint == config.token_mint @ TipError::InvalidTokenMint
-    )]
-    pub fee_vault_token_account: Account<'info, TokenAccount>,
-    #[account(
-        mut,
-        constraint = destination_token_account.mint == config.token_mint @ TipError::InvalidTokenMint
-    )]
-    pub destination_token_account: Account<'info, TokenAccount>,
-    pub token_program: Program<'info, Token>,
+fn update_group_members(
+    group_vault: &mut Account<GroupVault>,
+    members: Vec<Pubkey>,
+    threshold: u8,
+) -> Result<()> {
+    validate_group_members(&members, threshold)?;
+
+    group_vault.members = members;
+    group_vault.threshold = threshold;
+    group_vault.version = group_vault
+        .version
+        .checked_add(1)
+        .ok_or(TipError::InvalidGroupProposal)?;
+
+    emit!(GroupMembersUpdated {
+        group_vault: group_vault.key(),
+        members: group_vault.members.clone(),
+        threshold,
+        version: group_vault.version,
+    });
+
+    Ok(())
 }
 
-#[derive(Accounts)]
-pub struct SetRelayer<'info> {
-    #[account(
-        mut,
-        seeds = [b"config"],
-        bump,
-        has_one = upgrade_authority @ TipError::InvalidAuthority
-    )]
-    pub config: Account<'info, Config>,
-    pub upgrade_authority: Signer<'info>,
+fn update_withdraw_limit(vault: &mut Account<Vault>, daily_limit: u64, now: i64) -> Result<()> {
+    refresh_withdraw_limit(vault, now);
+
+    let current_limit = vault.daily_withdraw_limit;
+    let is_lowering = daily_limit != 0 && (current_limit == 0 || daily_limit <= current_limit);
+
+    if is_lowering {
+        vault.daily_withdraw_limit = daily_limit;
+        vault.pending_withdraw_limit = 0;
+        vault.pending_withdraw_limit_at = 0;
+    } else {
+        vault.pending_withdraw_limit = daily_limit;
+        vault.pending_withdraw_limit_at = now
+            .checked_add(WITHDRAW_DELAY_SECONDS)
+            .ok_or(TipError::InvalidTimestamp)?;
+    }
+
+    emit!(WithdrawLimitUpdated {
+        authority: vault.authority,
+        vault: vault.key(),
+        hashed_user_id: vault.hashed_user_id,
+        daily_withdraw_limit: vault.daily_withdraw_limit,
+        pending_withdraw_limit: vault.pending_withdraw_limit,
+        pending_withdraw_limit_at: vault.pending_withdraw_limit_at,
+    });
+
+    Ok(())
 }
 
-#[derive(Accounts)]
-pub struct SetFeeRate<'info> {
-    #[account(
-        mut,
-        seeds = [b"config"],
-        bump,
-        has_one = upgrade_authority @ TipError::InvalidAuthority
-    )]
-    pub config: Account<'info, Config>,
-    pub upgrade_authority: Signer<'info>,
+fn refresh_withdraw_limit(vault: &mut Vault, now: i64) {
+    if vault.pending_withdraw_limit_at != 0 && now >= vault.pending_withdraw_limit_at {
+        vault.daily_withdraw_limit = vault.pending_withdraw_limit;
+        vault.pending_withdraw_limit = 0;
+        vault.pending_withdraw_limit_at = 0;
+    }
+
+    if now
+        >= vault
+            .withdraw_window_start
+            .saturating_add(WITHDRAW_WINDOW_SECONDS)
+    {
+        vault.withdraw_window_start = now;
+        vault.withdrawn_in_window = 0;
+    }
 }
 
-#[derive(Accounts)]
-pub struct SetClaimAuthority<'info> {
-    #[account(
-        mut,
-        seeds = [b"config"],
-        bump,
-        has_one = upgrade_authority @ TipError::InvalidAuthority
-    )]
-    pub config: Account<'info, Config>,
-    pub upgrade_authority: Signer<'info>,
+fn consume_withdraw_limit(vault: &mut Vault, amount: u64, now: i64) -> Result<()> {
+    refresh_withdraw_limit(vault, now);
+
+    let withdrawn = vault
+        .withdrawn_in_window
+        .checked_add(amount)
+        .ok_or(TipError::WithdrawLimitExceeded)?;
+
+    if vault.daily_withdraw_limit != 0 {
+        require!(
+            withdrawn <= vault.daily_withdraw_limit,
+            TipError::WithdrawLimitExceeded
+        );
+    }
+
+    vault.withdrawn_in_window = withdrawn;
+
+    Ok(())
 }
 
-#[account]
-pub struct Config {
-    pub upgrade_authority: Pubkey,
-    pub relayer: Pubkey,
-    pub token_mint: Pubkey,
-    pub fee_bps: u16,
-    pub claim_authority: Pubkey,
+fn refund_withdraw_limit(vault: &mut Vault, amount: u64, charged_at: i64, now: i64) {
+    refresh_withdraw_limit(vault, now);
+
+    if charged_at >= vault.withdraw_window_start {
+        vault.withdrawn_in_window = vault.withdrawn_in_window.saturating_sub(amount);
+    }
 }
 
-impl Config {
-    pub const SPACE: usize = 8 + 32 + 32 + 32 + 2 + 32;
+fn check_rate_limit(max_tips: u64, window_seconds: i64) -> Result<()> {
+    require!(
+        (max_tips == 0 && window_seconds == 0)
+            || (max_tips > 0
+                && window_seconds > 0
+                && max_tips.checked_mul(window_seconds as u64).is_some()),
+        TipError::InvalidRateLimit
+    );
+
+    Ok(())
 }
 
-#[account]
-pub struct Vault {
-    pub authority: Pubkey,
-    pub hashed_user_id: [u8; 32],
-    pub token_mint: Pubkey,
-    pub claimed: bool,
+fn tip_rate_limit(vault: &Vault, config: &Config) -> (u64, u64) {
+    let (max_tips, window_seconds) = if vault.rate_limit_override {
+        (vault.rate_limit_max_tips, vault.rate_limit_window_seconds)
+    } else {
+        (config.rate_limit_max_tips, config.rate_limit_window_seconds)
+    };
+
+    (max_tips, window_seconds as u64)
 }
 
-impl Vault {
-    pub const SPACE: usize = 8 + 32 + 32 + 32 + 1;
+fn refill_tip_bucket(
+    vault: &Vault,
+    max_tips: u64,
+    window_seconds: u64,
+    now: i64,
+) -> Result<(u64, u64)> {
+    let capacity = max_tips
+        .checked_mul(window_seconds)
+        .ok_or(TipError::InvalidRateLimit)?;
+    let tokens = if vault.tip_bucket_updated_at == 0 {
+        capacity
+    } else {
+        let elapsed = now.saturating_sub(vault.tip_bucket_updated_at).max(0) as u64;
+        vault
+            .tip_bucket_tokens
+            .saturating_add(elapsed.saturating_mul(max_tips))
+            .min(capacity)
+    };
+
+    Ok((tokens, capacity))
 }
 
-#[account]
-pub struct FeeVault {
-    pub config: Pubkey,
-    pub token_mint: Pubkey,
-    pub bump: u8,
+fn consume_tip_rate_limit(
+    vault: &mut Vault,
+    config: &Config,
+    tip_count: u64,
+    now: i64,
+) -> Result<()> {
+    let (max_tips, window_seconds) = tip_rate_limit(vault, config);
+    if max_tips == 0 {
+        return Ok(());
+    }
+
+    let (tokens, _) = refill_tip_bucket(vault, max_tips, window_seconds, now)?;
+    let cost = tip_count
+        .checked_mul(window_seconds)
+        .ok_or(TipError::RateLimited)?;
+    require!(tokens >= cost, TipError::RateLimited);
+
+    vault.tip_bucket_tokens = tokens - cost;
+    vault.tip_bucket_updated_at = now;
+
+    Ok(())
 }
 
-impl FeeVault {
-    pub const SPACE: usize = 8 + 32 + 32 + 1;
+fn refund_tip_rate_limit(
+    vault: &mut Vault,
+    config: &Config,
+    tip_count: u64,
+    now: i64,
+) -> Result<()> {
+    let (max_tips, window_seconds) = tip_rate_limit(vault, config);
+    if max_tips == 0 {
+        return Ok(());
+    }
+
+    let (tokens, capacity) = refill_tip_bucket(vault, max_tips, window_seconds, now)?;
+    vault.tip_bucket_tokens = tokens
+        .saturating_add(tip_count.saturating_mul(window_seconds))
+        .min(capacity);
+    vault.tip_bucket_updated_at = now;
+
+    Ok(())
 }
 
-#[account]
-pub struct Allowance {
-    pub authority: Pubkey,
-    pub hashed_user_id: [u8; 32],
-    pub cap: u64,
-    pub remaining: u64,
+fn verify_tip_intent<T: AnchorSerialize>(
+    instructions_sysvar: Option<&UncheckedAccount>,
+    signer: &Pubkey,
+    domain: &[u8],
+    valid_until: i64,
+    intent: &T,
+) -> Result<()> {
+    let instructions_sysvar = instructions_sysvar.ok_or(TipError::MissingTipSignature)?;
+    require!(valid_until != 0, TipError::MissingIntentDeadline);
+
+    let mut message = domain.to_vec();
+    intent
+        .serialize(&mut message)
+        .map_err(|_| TipError::IntentMismatch)?;
+    verify_ed25519_instruction(instructions_sysvar, signer, &message)
 }
 
-impl Allowance {
-    pub const SPACE: usize = 8 + 32 + 32 + 8 + 8;
+fn verify_ed25519_instruction(
+    instructions_sysvar: &AccountInfo,
+    signer: &Pubkey,
+    message: &[u8],
+) -> Result<()> {
+    const HEADER_LEN: usize = 16;
+    const CURRENT_INSTRUCTION: u16 = u16::MAX;
+
+    let instruction = get_instruction_relative(-1, instructions_sysvar)
+        .map_err(|_| TipError::InvalidSignatureInstruction)?;
+    require_keys_eq!(
+        instruction.program_id,
+        ed25519_program::ID,
+        TipError::InvalidSignatureInstruction
+    );
+
+    let data = &instruction.data;
+    require!(
+        data.len() >= HEADER_LEN && data[0] == 1,
+        TipError::InvalidSignatureInstruction
+    );
+
+    let read_u16 = |offset: usize| u16::from_le_bytes([data[offset], data[offset + 1]]);
+    let signature_offset = read_u16(2) as usize;
+    let signature_instruction_index = read_u16(4);
+    let public_key_offset = read_u16(6) as usize;
+    let public_key_instruction_index = read_u16(8);
+    let message_offset = read_u16(10) as usize;
+    let message_size = read_u16(12) as usize;
+    let message_instruction_index = read_u16(14);
+
+    require!(
+        signature_instruction_index == CURRENT_INSTRUCTION
+            && public_key_instruction_index == CURRENT_INSTRUCTION
+            && message_instruction_index == CURRENT_INSTRUCTION,
+        TipError::InvalidSignatureInstruction
+    );
+    require!(
+        data.len() >= signature_offset + 64,
+        TipError::InvalidSignatureInstruction
+    );
+
+    let public_key = data
+        .get(public_key_offset..public_key_offset + 32)
+        .ok_or(TipError::InvalidSignatureInstruction)?;
+    require!(
+        public_key == signer.as_ref(),
+        TipError::InvalidSignatureInstruction
+    );
+
+    let signed_message = data
+        .get(message_offset..message_offset + message_size)
+        .ok_or(TipError::InvalidSignatureInstruction)?;
+    require!(signed_message == message, TipError::IntentMismatch);
+
+    Ok(())
 }
 
-fn calculate_fee(amount: u64, fee_bps: u16) -> Result<u64> {
-    if fee_bps == 0 {
-        return Ok(0);
+fn consume_sender_nonce(window: &mut NonceWindow, nonce: u64) -> Result<()> {
+    if nonce > window.highest {
+        let shift = nonce - window.highest;
+        window.bitmap = if shift >= NonceWindow::SIZE {
+            0
+        } else {
+            window.bitmap << shift
+        };
+        window.bitmap |= 1;
+        window.highest = nonce;
+        return Ok(());
+    }
+
+    let offset = window.highest - nonce;
+    require!(offset < NonceWindow::SIZE, TipError::StaleSenderNonce);
+
+    let bit = 1u64 << offset;
+    require!(window.bitmap & bit == 0, TipError::DuplicateSenderNonce);
+    window.bitmap |= bit;
+
+    Ok(())
+}
+
+fn record_tip_receipt(
+    tip_receipt: &mut TipReceipt,
+    tip_id: [u8; 32],
+    sender_vault: Pubkey,
+    payer: Pubkey,
+    now: i64,
+    bump: u8,
+) -> Result<()> {
+    require!(
+        tip_receipt.payer == Pubkey::default(),
+        TipError::DuplicateTipId
+    );
+
+    tip_receipt.tip_id = tip_id;
+    tip_receipt.sender_vault = sender_vault;
+    tip_receipt.payer = payer;
+    tip_receipt.created_at = now;
+    tip_receipt.bump = bump;
+
+    Ok(())
+}
+
+fn init_or_verify_fee_vault(
+    fee_vault: &mut FeeVault,
+    config: Pubkey,
+    token_mint: Pubkey,
+    bump: u8,
+) -> Result<()> {
+    if fee_vault.config == Pubkey::default() {
+        fee_vault.config = config;
+        fee_vault.token_mint = token_mint;
+        fee_vault.bump = bump;
+    } else {
+        require_keys_eq!(fee_vault.config, config, TipError::InvalidFeeVault);
+        require_keys_eq!(fee_vault.token_mint, token_mint, TipError::InvalidTokenMint);
+    }
+
+    Ok(())
+}
+
+fn init_or_verify_tip_escrow(
+    tip_escrow: &mut TipEscrow,
+    config: Pubkey,
+    token_mint: Pubkey,
+    bump: u8,
+) -> Result<()> {
+    if tip_escrow.config == Pubkey::default() {
+        tip_escrow.config = config;
+        tip_escrow.token_mint = token_mint;
+        tip_escrow.bump = bump;
+    } else {
+        require_keys_eq!(tip_escrow.config, config, TipError::InvalidTipEscrow);
+        require_keys_eq!(
+            tip_escrow.token_mint,
+            token_mint,
+            TipError::InvalidTokenMint
+        );
+    }
+
+    Ok(())
+}
+
+fn transfer_from_tip_escrow<'info>(
+    token_program: AccountInfo<'info>,
+    from: AccountInfo<'info>,
+    to: AccountInfo<'info>,
+    tip_escrow: AccountInfo<'info>,
+    config: &Pubkey,
+    tip_escrow_bump: u8,
+    amount: u64,
+) -> Result<()> {
+    let tip_escrow_seeds: [&[u8]; 3] = [b"tip_escrow", config.as_ref(), &[tip_escrow_bump]];
+    let signer_seeds: [&[&[u8]]; 1] = [&tip_escrow_seeds];
+
+    let cpi_accounts = Transfer {
+        from,
+        to,
+        authority: tip_escrow,
+    };
+
+    token::transfer(
+        CpiContext::new_with_signer(token_program, cpi_accounts, &signer_seeds),
+        amount,
+    )
+}
+
+fn init_or_verify_recipient_vault(
+    vault: &mut Vault,
+    hashed_user_id: [u8; 32],
+    token_mint: Pubkey,
+    unclaimed_authority: Pubkey,
+) -> Result<bool> {
+    let created = vault.hashed_user_id == [0u8; 32] && vault.token_mint == Pubkey::default();
+    if created {
+        vault.authority = unclaimed_authority;
+        vault.hashed_user_id = hashed_user_id;
+        vault.token_mint = token_mint;
+        vault.claimed = false;
+    } else {
+        require!(
+            vault.hashed_user_id == hashed_user_id,
+            TipError::InvalidRecipientPda
+        );
+        require_keys_eq!(vault.token_mint, token_mint, TipError::InvalidTokenMint);
+    }
+
+    if !vault.claimed {
+        require_keys_eq!(
+            vault.authority,
+            unclaimed_authority,
+            TipError::InvalidRecipientPda
+        );
+    }
+
+    Ok(created)
+}
+
+fn check_min_tip_amount(config: &Config, amount: u64, creates_vault: bool) -> Result<()> {
+    require!(amount >= config.min_tip_amount, TipError::TipBelowMinimum);
+    if creates_vault {
+        require!(
+            amount >= config.min_new_vault_tip_amount,
+            TipError::TipBelowMinimum
+        );
+    }
+
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn load_or_create_recipient_vault<'info>(
+    vault_info: &'info AccountInfo<'info>,
+    payer: &Signer<'info>,
+    system_program: &Program<'info, System>,
+    hashed_user_id: [u8; 32],
+    vault_bump: u8,
+    token_mint: Pubkey,
+    unclaimed_authority: Pubkey,
+    program_id: &Pubkey,
+) -> Result<bool> {
+    if vault_info.owner == program_id {
+        let expected_vault = Pubkey::create_program_address(
+            &[b"vault", hashed_user_id.as_ref(), &[vault_bump]],
+            program_id,
+        )
+        .map_err(|_| TipError::InvalidRecipientPda)?;
+        require_keys_eq!(
+            expected_vault,
+            vault_info.key(),
+            TipError::InvalidRecipientPda
+        );
+
+        let mut vault = Account::<Vault>::try_from(vault_info)?;
+        return init_or_verify_recipient_vault(
+            &mut vault,
+            hashed_user_id,
+            token_mint,
+            unclaimed_authority,
+        );
+    }
+
+    let (expected_vault, canonical_bump) =
+        Pubkey::find_program_address(&[b"vault", hashed_user_id.as_ref()], program_id);
+    require_keys_eq!(
+        expected_vault,
+        vault_info.key(),
+        TipError::InvalidRecipientPda
+    );
+    require!(canonical_bump == vault_bump, TipError::InvalidRecipientPda);
+
+    let vault_seeds: [&[u8]; 3] = [b"vault", hashed_user_id.as_ref(), &[vault_bump]];
+    let signer_seeds: [&[&[u8]]; 1] = [&vault_seeds];
+    let lamports = Rent::get()?.minimum_balance(Vault::SPACE);
+    let current_lamports = vault_info.lamports();
+
+    if current_lamports == 0 {
+        system_program::create_account(
+            CpiContext::new_with_signer(
+                system_program.to_account_info(),
+                system_program::CreateAccount {
+                    from: payer.to_account_info(),
+                    to: vault_info.clone(),
+                },
+                &signer_seeds,
+            ),
+            lamports,
+            Vault::SPACE as u64,
+            program_id,
+        )?;
+    } else {
+        let top_up = lamports.saturating_sub(current_lamports);
+        if top_up > 0 {
+            system_program::transfer(
+                CpiContext::new(
+                    system_program.to_account_info(),
+                    system_program::Transfer {
+                        from: payer.to_account_info(),
+                        to: vault_info.clone(),
+                    },
+                ),
+                top_up,
+            )?;
+        }
+        system_program::allocate(
+            CpiContext::new_with_signer(
+                system_program.to_account_info(),
+                system_program::Allocate {
+                    account_to_allocate: vault_info.clone(),
+                },
+                &signer_seeds,
+            ),
+            Vault::SPACE as u64,
+        )?;
+        system_program::assign(
+            CpiContext::new_with_signer(
+                system_program.to_account_info(),
+                system_program::Assign {
+                    account_to_assign: vault_info.clone(),
+                },
+                &signer_seeds,
+            ),
+            program_id,
+        )?;
     }
 
-    let numerator = (amount as u128)
-        .checked_mul(fee_bps as u128)
-        .ok_or(TipError::FeeCalculationOverflow)?;
-    let mut fee = numerator / 10_000;
+    let mut vault = Vault::default();
+    init_or_verify_recipient_vault(&mut vault, hashed_user_id, token_mint, unclaimed_authority)?;
+
+    let mut data = vault_info.try_borrow_mut_data()?;
+    vault.try_serialize(&mut &mut data[..])?;
 
-    if numerator % 10_000 != 0 {
-        fee = fee.checked_add(1).ok_or(TipError::FeeCalculationOverflow)?;
+    Ok(true)
+}
+
+fn realloc_legacy_account<'info>(
+    account: &AccountInfo<'info>,
+    payer: AccountInfo<'info>,
+    system_program: AccountInfo<'info>,
+    discriminator: &[u8],
+    space: usize,
+) -> Result<()> {
+    {
+        let data = account.try_borrow_data()?;
+        require!(
+            data.len() >= discriminator.len() && &data[..discriminator.len()] == discriminator,
+            TipError::InvalidMigrationAccount
+        );
+        require!(data.len() < space, TipError::AlreadyMigrated);
     }
 
-    if fee > u64::MAX as u128 {
-        return Err(TipError::FeeCalculationOverflow.into());
+    let lamports = Rent::get()?.minimum_balance(space);
+    let top_up = lamports.saturating_sub(account.lamports());
+    if top_up > 0 {
+        system_program::transfer(
+            CpiContext::new(
+                system_program,
+                system_program::Transfer {
+                    from: payer,
+                    to: account.clone(),
+                },
+            ),
+            top_up,
+        )?;
     }
 
-    Ok(fee as u64)
+    account.resize(space)?;
+
+    Ok(())
 }
 
 fn unclaimed_authority_key(program_id: &Pubkey) -> Pubkey {
@@ -923,6 +6600,158 @@ pub enum TipError {
     InvalidProgramData,
     #[msg("Program is missing an upgrade authority")]
     MissingUpgradeAuthority,
+    #[msg("Withdrawal exceeds the vault's daily limit")]
+    WithdrawLimitExceeded,
+    #[msg("Pending withdrawal account mismatch")]
+    InvalidPendingWithdrawal,
+    #[msg("Pending withdrawal is still in its delay period")]
+    WithdrawalNotReady,
+    #[msg("Timestamp arithmetic overflowed")]
+    InvalidTimestamp,
+    #[msg("Missing or malformed ed25519 signature instruction")]
+    InvalidSignatureInstruction,
+    #[msg("Signed intent does not match the instruction")]
+    IntentMismatch,
+    #[msg("Signed intent has expired")]
+    IntentExpired,
+    #[msg("Signed intent nonce has already been used")]
+    InvalidIntentNonce,
+    #[msg("Allowance period must not be negative")]
+    InvalidAllowancePeriod,
+    #[msg("Allowance has expired")]
+    AllowanceExpired,
+    #[msg("Tip amount exceeds the allowance per-tip limit")]
+    TipExceedsPerTipLimit,
+    #[msg("Allowance delta is zero or out of range")]
+    InvalidAllowanceDelta,
+    #[msg("Allowance remaining does not match the expected value")]
+    AllowanceRemainingMismatch,
+    #[msg("Invalid allowance spender")]
+    InvalidSpender,
+    #[msg("Allowance recipient rules account is missing or mismatched")]
+    MissingRecipientRules,
+    #[msg("Recipient is not permitted by the allowance recipient rules")]
+    RecipientNotAllowed,
+    #[msg("Recipient rule mode does not match the existing rules")]
+    RecipientRuleModeMismatch,
+    #[msg("Allowance recipient rules are full")]
+    TooManyRecipientRules,
+    #[msg("Invalid session key")]
+    InvalidSessionKey,
+    #[msg("Session expiry must be in the future and within the maximum duration")]
+    InvalidSessionExpiry,
+    #[msg("Session key has expired")]
+    SessionExpired,
+    #[msg("Session budget is insufficient for this tip")]
+    SessionBudgetExceeded,
+    #[msg("Tip id has already been used")]
+    DuplicateTipId,
+    #[msg("Tip receipt account mismatch")]
+    InvalidTipReceipt,
+    #[msg("Tip receipt is still within its retention period")]
+    TipReceiptNotExpired,
+    #[msg("Sender nonce is older than the accepted window")]
+    StaleSenderNonce,
+    #[msg("Sender nonce has already been used")]
+    DuplicateSenderNonce,
+    #[msg("Memo exceeds the maximum length")]
+    MemoTooLong,
+    #[msg("Recipient list is empty, too long, or does not match the remaining accounts")]
+    InvalidRecipientCount,
+    #[msg("Tip deadline has passed")]
+    TipExpired,
+    #[msg("Allowance approval deadline has passed")]
+    ApprovalExpired,
+    #[msg("Vault requires a tip intent signed by its authority")]
+    MissingTipSignature,
+    #[msg("Rate limit must set both tip count and window, or neither")]
+    InvalidRateLimit,
+    #[msg("Sender has exceeded the tip rate limit")]
+    RateLimited,
+    #[msg("Tip amount is below the configured minimum")]
+    TipBelowMinimum,
+    #[msg("Invalid tip destination")]
+    InvalidDestination,
+    #[msg("Dispute window must be between 0 and 7 days")]
+    InvalidDisputeWindow,
+    #[msg("Tips must be escrowed while a dispute window is configured")]
+    TipEscrowRequired,
+    #[msg("Tip escrow is disabled")]
+    TipEscrowDisabled,
+    #[msg("Invalid tip escrow account")]
+    InvalidTipEscrow,
+    #[msg("Invalid pending tip")]
+    InvalidPendingTip,
+    #[msg("Tip is still within its dispute window")]
+    TipDisputeWindowOpen,
+    #[msg("Tip dispute window has closed")]
+    TipDisputeWindowClosed,
+    #[msg("Subscription interval must be at least one hour")]
+    InvalidSubscriptionInterval,
+    #[msg("Subscription must run for at least one period")]
+    InvalidSubscriptionPeriods,
+    #[msg("Subscription payment is not due yet")]
+    SubscriptionNotDue,
+    #[msg("Subscription has no remaining periods")]
+    SubscriptionFinished,
+    #[msg("Pool target must be greater than zero")]
+    InvalidPoolTarget,
+    #[msg("Pool deadline must be in the future")]
+    InvalidPoolDeadline,
+    #[msg("Pool deadline has passed")]
+    PoolExpired,
+    #[msg("Pool has already been claimed")]
+    PoolAlreadyClaimed,
+    #[msg("Pool has not reached its target")]
+    PoolNotFunded,
+    #[msg("Pool reached its target and cannot be refunded")]
+    PoolAlreadyFunded,
+    #[msg("Pool is still open")]
+    PoolStillOpen,
+    #[msg("Invalid pool contribution")]
+    InvalidPoolContribution,
+    #[msg("Bounty amount must be greater than zero")]
+    InvalidBountyAmount,
+    #[msg("Invalid merchant")]
+    InvalidMerchant,
+    #[msg("Invoice has already been paid")]
+    InvoiceAlreadyPaid,
+    #[msg("Invoice has expired")]
+    InvoiceExpired,
+    #[msg("Sender is not the invoice payer")]
+    InvalidInvoicePayer,
+    #[msg("Group members must be 1-10 distinct keys")]
+    InvalidGroupMembers,
+    #[msg("Group threshold must be between 1 and the member count")]
+    InvalidGroupThreshold,
+    #[msg("Signer is not a group member")]
+    NotGroupMember,
+    #[msg("Group membership changed since this proposal was created")]
+    StaleGroupProposal,
+    #[msg("Group proposal has not reached its approval threshold")]
+    GroupProposalNotApproved,
+    #[msg("Invalid group proposal")]
+    InvalidGroupProposal,
+    #[msg("Recipient appears more than once in the batch")]
+    DuplicateRecipient,
+    #[msg("Signed tip intents must set valid_until")]
+    MissingIntentDeadline,
+    #[msg("Pool has not been claimed")]
+    PoolNotClaimed,
+    #[msg("Pool still has open contributions")]
+    PoolHasContributions,
+    #[msg("Pool token account must be closed with the pool")]
+    MissingPoolTokenAccount,
+    #[msg("Stray pool tokens need a creator token account to sweep into")]
+    MissingCreatorTokenAccount,
+    #[msg("Invoice is still payable or within its retention window")]
+    InvoiceNotClosable,
+    #[msg("Allowances with recipient rules cannot pay wallets")]
+    WalletRecipientRestricted,
+    #[msg("Account is not a legacy account of the expected type")]
+    InvalidMigrationAccount,
+    #[msg("Account already has the current layout")]
+    AlreadyMigrated,
 }
 
 #[event]
@@ -940,16 +6769,22 @@ pub struct DepositEvent {
     pub vault_bump: u8,
     pub hashed_user_id: [u8; 32],
     pub amount: u64,
+    pub funder: Pubkey,
 }
 
 #[event]
 pub struct AllowanceUpdated {
     pub authority: Pubkey,
+    pub spender: Pubkey,
     pub vault: Pubkey,
     pub vault_bump: u8,
     pub hashed_user_id: [u8; 32],
     pub cap: u64,
     pub remaining: u64,
+    pub period_seconds: i64,
+    pub period_start: i64,
+    pub expires_at: i64,
+    pub max_per_tip: u64,
 }
 
 #[event]
@@ -970,6 +6805,7 @@ pub struct TipEvent {
     pub fee_amount: u64,
     pub fee_bps: u16,
     pub total_amount: u64,
+    pub memo: Option<String>,
 }
 
 #[event]
@@ -982,3 +6818,399 @@ pub struct WithdrawEvent {
     pub destination_token_account: Pubkey,
     pub amount: u64,
 }
+
+#[event]
+pub struct WithdrawLimitUpdated {
+    pub authority: Pubkey,
+    pub vault: Pubkey,
+    pub hashed_user_id: [u8; 32],
+    pub daily_withdraw_limit: u64,
+    pub pending_withdraw_limit: u64,
+    pub pending_withdraw_limit_at: i64,
+}
+
+#[event]
+pub struct WithdrawalQueued {
+    pub authority: Pubkey,
+    pub vault: Pubkey,
+    pub hashed_user_id: [u8; 32],
+    pub pending_withdrawal: Pubkey,
+    pub id: u64,
+    pub destination: Pubkey,
+    pub amount: u64,
+    pub executable_at: i64,
+}
+
+#[event]
+pub struct WithdrawalCancelled {
+    pub authority: Pubkey,
+    pub vault: Pubkey,
+    pub hashed_user_id: [u8; 32],
+    pub pending_withdrawal: Pubkey,
+    pub id: u64,
+    pub amount: u64,
+}
+
+#[event]
+pub struct RelayedWithdrawEvent {
+    pub relayer: Pubkey,
+    pub authority: Pubkey,
+    pub vault: Pubkey,
+    pub vault_bump: u8,
+    pub hashed_user_id: [u8; 32],
+    pub destination: Pubkey,
+    pub destination_token_account: Pubkey,
+    pub amount: u64,
+    pub relayer_fee: u64,
+    pub nonce: u64,
+}
+
+#[event]
+pub struct RecipientRulesUpdated {
+    pub authority: Pubkey,
+    pub spender: Pubkey,
+    pub allowance: Pubkey,
+    pub hashed_user_id: [u8; 32],
+    pub enabled: bool,
+    pub mode: RecipientRuleMode,
+    pub recipients: Vec<[u8; 32]>,
+}
+
+#[event]
+pub struct SessionCreated {
+    pub authority: Pubkey,
+    pub vault: Pubkey,
+    pub hashed_user_id: [u8; 32],
+    pub session: Pubkey,
+    pub session_key: Pubkey,
+    pub cap: u64,
+    pub expires_at: i64,
+}
+
+#[event]
+pub struct SessionRevoked {
+    pub authority: Pubkey,
+    pub vault: Pubkey,
+    pub hashed_user_id: [u8; 32],
+    pub session: Pubkey,
+    pub session_key: Pubkey,
+    pub remaining: u64,
+}
+
+#[event]
+pub struct TipSignatureRequirementUpdated {
+    pub authority: Pubkey,
+    pub vault: Pubkey,
+    pub hashed_user_id: [u8; 32],
+    pub require_tip_signature: bool,
+}
+
+#[event]
+pub struct RateLimitUpdated {
+    pub max_tips: u64,
+    pub window_seconds: i64,
+}
+
+#[event]
+pub struct VaultRateLimitUpdated {
+    pub vault: Pubkey,
+    pub hashed_user_id: [u8; 32],
+    pub overridden: bool,
+    pub max_tips: u64,
+    pub window_seconds: i64,
+}
+
+#[event]
+pub struct MinTipAmountsUpdated {
+    pub min_tip_amount: u64,
+    pub min_new_vault_tip_amount: u64,
+}
+
+#[event]
+pub struct WalletTipEvent {
+    pub relayer: Pubkey,
+    pub sender_vault: Pubkey,
+    pub sender_vault_bump: u8,
+    pub sender_hashed_user_id: [u8; 32],
+    pub destination_token_account: Pubkey,
+    pub destination_owner: Pubkey,
+    pub amount: u64,
+    pub sender_nonce: u64,
+    pub allowance_remaining: u64,
+    pub tip_id: [u8; 32],
+    pub fee_vault: Pubkey,
+    pub fee_vault_bump: u8,
+    pub fee_amount: u64,
+    pub fee_bps: u16,
+    pub total_amount: u64,
+    pub memo: Option<String>,
+}
+
+#[event]
+pub struct TipDisputeWindowUpdated {
+    pub window_seconds: i64,
+}
+
+#[event]
+pub struct TipEscrowed {
+    pub relayer: Pubkey,
+    pub pending_tip: Pubkey,
+    pub sender_vault: Pubkey,
+    pub recipient_vault: Pubkey,
+    pub sender_hashed_user_id: [u8; 32],
+    pub recipient_hashed_user_id: [u8; 32],
+    pub amount: u64,
+    pub sender_nonce: u64,
+    pub allowance_remaining: u64,
+    pub tip_id: [u8; 32],
+    pub fee_amount: u64,
+    pub fee_bps: u16,
+    pub total_amount: u64,
+    pub finalizable_at: i64,
+    pub memo: Option<String>,
+}
+
+#[event]
+pub struct TipFinalized {
+    pub pending_tip: Pubkey,
+    pub tip_id: [u8; 32],
+    pub sender_vault: Pubkey,
+    pub recipient_vault: Pubkey,
+    pub sender_hashed_user_id: [u8; 32],
+    pub recipient_hashed_user_id: [u8; 32],
+    pub amount: u64,
+    pub fee_amount: u64,
+}
+
+#[event]
+pub struct TipReversed {
+    pub pending_tip: Pubkey,
+    pub reversed_by: Pubkey,
+    pub tip_id: [u8; 32],
+    pub sender_vault: Pubkey,
+    pub recipient_vault: Pubkey,
+    pub sender_hashed_user_id: [u8; 32],
+    pub recipient_hashed_user_id: [u8; 32],
+    pub amount: u64,
+    pub fee_amount: u64,
+    pub allowance_remaining: u64,
+}
+
+#[event]
+pub struct SubscriptionCreated {
+    pub authority: Pubkey,
+    pub subscription: Pubkey,
+    pub sender_vault: Pubkey,
+    pub sender_hashed_user_id: [u8; 32],
+    pub recipient_hashed_user_id: [u8; 32],
+    pub amount: u64,
+    pub interval_seconds: i64,
+    pub next_due: i64,
+    pub remaining_periods: u64,
+}
+
+#[event]
+pub struct SubscriptionCancelled {
+    pub authority: Pubkey,
+    pub subscription: Pubkey,
+    pub sender_vault: Pubkey,
+    pub sender_hashed_user_id: [u8; 32],
+    pub recipient_hashed_user_id: [u8; 32],
+    pub remaining_periods: u64,
+}
+
+#[event]
+pub struct SubscriptionPayment {
+    pub subscription: Pubkey,
+    pub cranker: Pubkey,
+    pub sender_vault: Pubkey,
+    pub recipient_vault: Pubkey,
+    pub sender_hashed_user_id: [u8; 32],
+    pub recipient_hashed_user_id: [u8; 32],
+    pub amount: u64,
+    pub fee_vault: Pubkey,
+    pub fee_amount: u64,
+    pub fee_bps: u16,
+    pub total_amount: u64,
+    pub next_due: i64,
+    pub remaining_periods: u64,
+}
+
+#[event]
+pub struct PoolCreated {
+    pub creator: Pubkey,
+    pub pool: Pubkey,
+    pub pool_id: [u8; 32],
+    pub recipient_hashed_user_id: [u8; 32],
+    pub target_amount: u64,
+    pub deadline: i64,
+}
+
+#[event]
+pub struct PoolContributed {
+    pub relayer: Pubkey,
+    pub pool: Pubkey,
+    pub pool_id: [u8; 32],
+    pub sender_vault: Pubkey,
+    pub sender_hashed_user_id: [u8; 32],
+    pub amount: u64,
+    pub sender_nonce: u64,
+    pub allowance_remaining: u64,
+    pub fee_amount: u64,
+    pub fee_bps: u16,
+    pub total_amount: u64,
+    pub contribution_amount: u64,
+    pub pool_total_contributed: u64,
+}
+
+#[event]
+pub struct PoolClaimed {
+    pub pool: Pubkey,
+    pub pool_id: [u8; 32],
+    pub recipient_vault: Pubkey,
+    pub recipient_hashed_user_id: [u8; 32],
+    pub amount: u64,
+    pub fee_amount: u64,
+}
+
+#[event]
+pub struct PoolRefunded {
+    pub pool: Pubkey,
+    pub pool_id: [u8; 32],
+    pub sender_vault: Pubkey,
+    pub amount: u64,
+    pub fee_amount: u64,
+}
+
+#[event]
+pub struct BountyCreated {
+    pub creator: Pubkey,
+    pub bounty: Pubkey,
+    pub bounty_id: [u8; 32],
+    pub creator_vault: Pubkey,
+    pub creator_hashed_user_id: [u8; 32],
+    pub amount: u64,
+}
+
+#[event]
+pub struct BountyCancelled {
+    pub creator: Pubkey,
+    pub bounty: Pubkey,
+    pub bounty_id: [u8; 32],
+    pub creator_vault: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct BountyAwarded {
+    pub creator: Pubkey,
+    pub awarded_by: Pubkey,
+    pub bounty: Pubkey,
+    pub bounty_id: [u8; 32],
+    pub creator_vault: Pubkey,
+    pub recipient_vault: Pubkey,
+    pub recipient_hashed_user_id: [u8; 32],
+    pub amount: u64,
+}
+
+#[event]
+pub struct MerchantUpdated {
+    pub merchant: Pubkey,
+    pub authority: Pubkey,
+    pub payout_token_account: Pubkey,
+    pub active: bool,
+}
+
+#[event]
+pub struct InvoiceCreated {
+    pub invoice: Pubkey,
+    pub invoice_id: [u8; 32],
+    pub creator: Pubkey,
+    pub recipient_hashed_user_id: [u8; 32],
+    pub merchant: Pubkey,
+    pub payer_hashed_user_id: [u8; 32],
+    pub amount: u64,
+    pub expires_at: i64,
+    pub reference: [u8; 32],
+}
+
+#[event]
+pub struct InvoiceCancelled {
+    pub invoice: Pubkey,
+    pub invoice_id: [u8; 32],
+    pub creator: Pubkey,
+    pub reference: [u8; 32],
+}
+
+#[event]
+pub struct InvoicePaid {
+    pub relayer: Pubkey,
+    pub invoice: Pubkey,
+    pub invoice_id: [u8; 32],
+    pub reference: [u8; 32],
+    pub merchant: Pubkey,
+    pub recipient_hashed_user_id: [u8; 32],
+    pub sender_vault: Pubkey,
+    pub sender_hashed_user_id: [u8; 32],
+    pub destination_token_account: Pubkey,
+    pub amount: u64,
+    pub sender_nonce: u64,
+    pub allowance_remaining: u64,
+    pub fee_amount: u64,
+    pub fee_bps: u16,
+    pub total_amount: u64,
+}
+
+#[event]
+pub struct GroupVaultCreated {
+    pub group_vault: Pubkey,
+    pub vault: Pubkey,
+    pub group_id: [u8; 32],
+    pub members: Vec<Pubkey>,
+    pub threshold: u8,
+}
+
+#[event]
+pub struct GroupProposalCreated {
+    pub group_vault: Pubkey,
+    pub proposal: Pubkey,
+    pub proposal_id: u64,
+    pub proposer: Pubkey,
+    pub action: GroupProposalAction,
+    pub version: u64,
+}
+
+#[event]
+pub struct GroupProposalApproved {
+    pub group_vault: Pubkey,
+    pub proposal: Pubkey,
+    pub proposal_id: u64,
+    pub member: Pubkey,
+    pub approvals: u8,
+    pub threshold: u8,
+}
+
+#[event]
+pub struct GroupProposalExecuted {
+    pub group_vault: Pubkey,
+    pub proposal: Pubkey,
+    pub proposal_id: u64,
+    pub executed_by: Pubkey,
+    pub action: GroupProposalAction,
+}
+
+#[event]
+pub struct GroupProposalCancelled {
+    pub group_vault: Pubkey,
+    pub proposal: Pubkey,
+    pub proposal_id: u64,
+    pub proposer: Pubkey,
+}
+
+#[event]
+pub struct GroupMembersUpdated {
+    pub group_vault: Pubkey,
+    pub members: Vec<Pubkey>,
+    pub threshold: u8,
+    pub version: u64,
+}
@@ -9,12 +9,14 @@ import {
 } from '@solana/web3.js';
 import assert from 'assert';
 import BN from 'bn.js';
-import { createHash } from 'node:crypto';
+import { createHash, randomBytes } from 'node:crypto';
 
 import idl from '../../../target/idl/tipcoin.json' with { type: 'json' };
 import type { Tipcoin } from '../../../target/types/tipcoin.js';
 
 const TOKEN_PROGRAM_ID = new PublicKey('TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA');
+const BPF_LOADER_UPGRADEABLE_ID = new PublicKey('BPFLoaderUpgradeab1e11111111111111111111111');
+const ASSOCIATED_TOKEN_PROGRAM_ID = new PublicKey('ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL');
 const MINT_SIZE = 82;
 const TOKEN_ACCOUNT_SIZE = 165;
 
@@ -193,7 +195,7 @@ describe('tipcoin program', () => {
       program.programId,
     );
     const [allowancePda] = PublicKey.findProgramAddressSync(
-      [Buffer.from('allowance'), hashedUserId],
+      [Buffer.from('allowance'), hashedUserId, relayer.toBuffer()],
       program.programId,
     );
 
@@ -213,6 +215,7 @@ describe('tipcoin program', () => {
     assert.strictEqual(vaultAccount.authority.toBase58(), wallet.publicKey.toBase58());
     assert.strictEqual(vaultAccount.tokenMint.toBase58(), tokenMint.toBase58());
     assert.strictEqual(allowanceAccount.authority.toBase58(), wallet.publicKey.toBase58());
+    assert.strictEqual(allowanceAccount.spender.toBase58(), relayer.toBase58());
 
     const userTokenAccount = await createTokenAccount(
       provider,
@@ -276,9 +279,19 @@ describe('tipcoin program', () => {
 
     const newAllowanceAmount = new BN(750_000);
 
+    const allowancePeriodSeconds = new BN(24 * 60 * 60);
+
     await program.methods
-      .approveAllowance(newAllowanceAmount)
+      .approveAllowance({
+        spender: relayer,
+        amount: newAllowanceAmount,
+        periodSeconds: allowancePeriodSeconds,
+        expiresAt: new BN(0),
+        maxPerTip: new BN(0),
+        validUntil: new BN(0),
+      })
       .accountsPartial({
+        vault: vaultPda,
         allowance: allowancePda,
       })
       .rpc();
@@ -286,6 +299,10 @@ describe('tipcoin program', () => {
     const updatedAllowanceAccount = await program.account.allowance.fetch(allowancePda);
     assert.strictEqual(updatedAllowanceAccount.cap.toString(), newAllowanceAmount.toString());
     assert.strictEqual(updatedAllowanceAccount.remaining.toString(), newAllowanceAmount.toString());
+    assert.strictEqual(
+      updatedAllowanceAccount.periodSeconds.toString(),
+      allowancePeriodSeconds.toString(),
+    );
 
     const recipientHashedSeed = `recipient-${Date.now()}-${Math.random()}`;
     const recipientHashedId = createHash('sha256').update(recipientHashedSeed).digest();
@@ -316,6 +333,10 @@ describe('tipcoin program', () => {
       tokenMint,
       feeVaultPda,
     );
+    const [tipReceiptPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from('tip_receipt'), vaultPda.toBuffer(), tipId],
+      program.programId,
+    );
 
     const tipEvents: Array<{ event: unknown; slot: number }> = [];
     const tipListener = program.addEventListener('tipEvent', (event, slot) => {
@@ -327,19 +348,24 @@ describe('tipcoin program', () => {
         .tip(
           tipAmount,
           Array.from(tipId) as number[],
+          new BN(1),
           null,
           Array.from(recipientHashedId) as number[],
+          new BN(0),
         )
         .accountsPartial({
           config: configPda,
           relayer: relayerKeypair.publicKey,
           senderVault: vaultPda,
           senderAllowance: allowancePda,
+          recipientRules: null,
           recipientVault: recipientVaultPda,
           senderVaultTokenAccount: vaultTokenAccount,
           recipientVaultTokenAccount,
           feeVault: feeVaultPda,
+          tipReceipt: tipReceiptPda,
           feeVaultTokenAccount,
+          instructionsSysvar: null,
           tokenProgram: TOKEN_PROGRAM_ID,
           systemProgram: SystemProgram.programId,
           rent: anchor.web3.SYSVAR_RENT_PUBKEY,
@@ -423,6 +449,10 @@ describe('tipcoin program', () => {
       'Allowance remaining mismatch after tip',
     );
 
+    const tipReceipt = await program.account.tipReceipt.fetch(tipReceiptPda);
+    assert.deepStrictEqual(tipReceipt.tipId, Array.from(tipId));
+    assert.strictEqual(tipReceipt.payer.toBase58(), relayer.toBase58());
+
     const parsedTipEvent = tipEvents
       .map(
         (record) =>
@@ -448,4 +478,1754 @@ describe('tipcoin program', () => {
     assert.strictEqual(parsedTipEvent!.totalAmount.toString(), totalTipCost.toString());
     assert.strictEqual(parsedTipEvent!.feeBps, feeBps);
   });
+
+  describe('vault safety checks', () => {
+    const [configPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from('config')],
+      program.programId,
+    );
+    const relayerKeypair = Keypair.generate();
+    const feeVault = PublicKey.findProgramAddressSync(
+      [Buffer.from('fee_vault'), configPda.toBuffer()],
+      program.programId,
+    )[0];
+    let tokenMint: PublicKey;
+    let feeBps: number;
+    let feeVaultTokenAccount: PublicKey;
+
+    type VaultAccounts = { hashedUserId: Buffer; vault: PublicKey; vaultTokenAccount: PublicKey };
+
+    const findPda = (...seeds: Array<Buffer | Uint8Array>): PublicKey =>
+      PublicKey.findProgramAddressSync(seeds, program.programId)[0];
+
+    const expectError = async (promise: Promise<unknown>, code: string): Promise<void> => {
+      await assert.rejects(promise, (err: unknown) => {
+        assert.match(String(err), new RegExp(code));
+        return true;
+      });
+    };
+
+    const sleep = (ms: number) => new Promise((resolve) => setTimeout(resolve, ms));
+
+    const feeFor = (amount: BN): BN => amount.muln(feeBps).addn(9_999).divn(10_000);
+
+    const claimFundedVault = async (amount: bigint): Promise<VaultAccounts> => {
+      const hashedUserId = createHash('sha256')
+        .update(`limits-user-${Date.now()}-${Math.random()}`)
+        .digest();
+      const vault = findPda(Buffer.from('vault'), hashedUserId);
+
+      await program.methods
+        .claimVault(Array.from(hashedUserId) as number[])
+        .accountsPartial({
+          authority: wallet.publicKey,
+          claimAuthority: wallet.publicKey,
+          config: configPda,
+          vault,
+          allowance: findPda(
+            Buffer.from('allowance'),
+            hashedUserId,
+            relayerKeypair.publicKey.toBuffer(),
+          ),
+          tokenMint,
+        })
+        .rpc();
+
+      const vaultTokenAccount = await createTokenAccount(
+        provider,
+        wallet.publicKey,
+        tokenMint,
+        vault,
+      );
+      await provider.sendAndConfirm(
+        new Transaction().add(
+          createMintToInstruction(tokenMint, vaultTokenAccount, wallet.publicKey, amount),
+        ),
+      );
+
+      return { hashedUserId, vault, vaultTokenAccount };
+    };
+
+    const allowanceFor = (sender: VaultAccounts): PublicKey =>
+      findPda(Buffer.from('allowance'), sender.hashedUserId, relayerKeypair.publicKey.toBuffer());
+
+    const claimTippingVault = async (): Promise<VaultAccounts> => {
+      const sender = await claimFundedVault(1_000_000n);
+      await program.methods
+        .approveAllowance({
+          spender: relayerKeypair.publicKey,
+          amount: new BN(1_000_000),
+          periodSeconds: new BN(0),
+          expiresAt: new BN(0),
+          maxPerTip: new BN(0),
+          validUntil: new BN(0),
+        })
+        .accountsPartial({
+          authority: wallet.publicKey,
+          vault: sender.vault,
+          allowance: allowanceFor(sender),
+        })
+        .rpc();
+      return sender;
+    };
+
+    const createRecipient = async (): Promise<VaultAccounts> => {
+      const hashedUserId = randomBytes(32);
+      const vault = findPda(Buffer.from('vault'), hashedUserId);
+      const vaultTokenAccount = await createTokenAccount(
+        provider,
+        wallet.publicKey,
+        tokenMint,
+        vault,
+      );
+      return { hashedUserId, vault, vaultTokenAccount };
+    };
+
+    const airdrop = async (to: PublicKey): Promise<void> => {
+      await connection.confirmTransaction(
+        await connection.requestAirdrop(to, anchor.web3.LAMPORTS_PER_SOL),
+        'confirmed',
+      );
+    };
+
+    const createFundedTokenAccount = async (
+      owner: PublicKey,
+      amount: bigint,
+    ): Promise<PublicKey> => {
+      const tokenAccount = await createTokenAccount(provider, wallet.publicKey, tokenMint, owner);
+      await provider.sendAndConfirm(
+        new Transaction().add(
+          createMintToInstruction(tokenMint, tokenAccount, wallet.publicKey, amount),
+        ),
+      );
+      return tokenAccount;
+    };
+
+    const tokenBalance = async (tokenAccount: PublicKey): Promise<string> =>
+      (await connection.getTokenAccountBalance(tokenAccount)).value.amount;
+
+    const associatedTokenAddress = (owner: PublicKey): PublicKey =>
+      PublicKey.findProgramAddressSync(
+        [owner.toBuffer(), TOKEN_PROGRAM_ID.toBuffer(), tokenMint.toBuffer()],
+        ASSOCIATED_TOKEN_PROGRAM_ID,
+      )[0];
+
+    const approveSpender = (sender: VaultAccounts, spender: PublicKey, amount: BN) =>
+      program.methods
+        .approveAllowance({
+          spender,
+          amount,
+          periodSeconds: new BN(0),
+          expiresAt: new BN(0),
+          maxPerTip: new BN(0),
+          validUntil: new BN(0),
+        })
+        .accountsPartial({
+          authority: wallet.publicKey,
+          vault: sender.vault,
+          allowance: findPda(Buffer.from('allowance'), sender.hashedUserId, spender.toBuffer()),
+        })
+        .rpc();
+
+    const tipToWallet = (
+      sender: VaultAccounts,
+      spender: Keypair,
+      destinationTokenAccount: PublicKey,
+      amount: BN,
+      nonce: number,
+    ) => {
+      const tipId = randomBytes(32);
+      return program.methods
+        .tipToWallet(amount, Array.from(tipId) as number[], new BN(nonce), null, new BN(0))
+        .accountsPartial({
+          config: configPda,
+          relayer: spender.publicKey,
+          senderVault: sender.vault,
+          senderAllowance: findPda(
+            Buffer.from('allowance'),
+            sender.hashedUserId,
+            spender.publicKey.toBuffer(),
+          ),
+          feeVault,
+          tipReceipt: findPda(Buffer.from('tip_receipt'), sender.vault.toBuffer(), tipId),
+          senderVaultTokenAccount: sender.vaultTokenAccount,
+          destinationTokenAccount,
+          feeVaultTokenAccount,
+          instructionsSysvar: null,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([spender])
+        .rpc();
+    };
+
+    const createPool = async (recipient: VaultAccounts, targetAmount: BN, deadline: number) => {
+      const poolId = randomBytes(32);
+      const pool = findPda(Buffer.from('pool'), wallet.publicKey.toBuffer(), poolId);
+      const poolTokenAccount = findPda(Buffer.from('pool_token'), pool.toBuffer());
+      await program.methods
+        .createPool(
+          Array.from(poolId) as number[],
+          Array.from(recipient.hashedUserId) as number[],
+          targetAmount,
+          new BN(deadline),
+        )
+        .accountsPartial({
+          config: configPda,
+          creator: wallet.publicKey,
+          pool,
+          poolTokenAccount,
+          tokenMint,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+      return { pool, poolTokenAccount };
+    };
+
+    const payInvoice = (
+      sender: VaultAccounts,
+      invoice: PublicKey,
+      destinationTokenAccount: PublicKey,
+      nonce: number,
+      options: { merchant?: PublicKey | null; recipientRules?: PublicKey | null } = {},
+    ) =>
+      program.methods
+        .payInvoice(new BN(nonce), new BN(0))
+        .accountsPartial({
+          config: configPda,
+          relayer: relayerKeypair.publicKey,
+          senderVault: sender.vault,
+          senderAllowance: allowanceFor(sender),
+          recipientRules: options.recipientRules ?? null,
+          invoice,
+          merchant: options.merchant ?? null,
+          destinationTokenAccount,
+          feeVault,
+          senderVaultTokenAccount: sender.vaultTokenAccount,
+          feeVaultTokenAccount,
+          instructionsSysvar: null,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([relayerKeypair])
+        .rpc();
+
+    type TipOptions = {
+      amount?: BN;
+      nonce: number;
+      tipId?: Buffer;
+      validUntil?: BN;
+      instructionsSysvar?: PublicKey | null;
+      preInstructions?: TransactionInstruction[];
+      spender?: Keypair;
+      recipientRules?: PublicKey | null;
+    };
+
+    const sendTip = (
+      sender: VaultAccounts,
+      recipient: VaultAccounts,
+      options: TipOptions,
+    ) => {
+      const tipId = options.tipId ?? randomBytes(32);
+      const spender = options.spender ?? relayerKeypair;
+      return program.methods
+        .tip(
+          options.amount ?? new BN(10_000),
+          Array.from(tipId) as number[],
+          new BN(options.nonce),
+          null,
+          Array.from(recipient.hashedUserId) as number[],
+          options.validUntil ?? new BN(0),
+        )
+        .accountsPartial({
+          config: configPda,
+          relayer: spender.publicKey,
+          senderVault: sender.vault,
+          senderAllowance: findPda(
+            Buffer.from('allowance'),
+            sender.hashedUserId,
+            spender.publicKey.toBuffer(),
+          ),
+          recipientRules: options.recipientRules ?? null,
+          recipientVault: recipient.vault,
+          feeVault,
+          tipReceipt: findPda(Buffer.from('tip_receipt'), sender.vault.toBuffer(), tipId),
+          senderVaultTokenAccount: sender.vaultTokenAccount,
+          recipientVaultTokenAccount: recipient.vaultTokenAccount,
+          feeVaultTokenAccount,
+          instructionsSysvar: options.instructionsSysvar ?? null,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+          rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+        })
+        .preInstructions(options.preInstructions ?? [])
+        .signers([spender])
+        .rpc();
+    };
+
+    before(async () => {
+      await connection.confirmTransaction(
+        await connection.requestAirdrop(relayerKeypair.publicKey, 2 * anchor.web3.LAMPORTS_PER_SOL),
+        'confirmed',
+      );
+
+      if (!(await connection.getAccountInfo(configPda))) {
+        const mintKeypair = Keypair.generate();
+        await provider.sendAndConfirm(
+          new Transaction().add(
+            SystemProgram.createAccount({
+              fromPubkey: wallet.publicKey,
+              newAccountPubkey: mintKeypair.publicKey,
+              lamports: await connection.getMinimumBalanceForRentExemption(MINT_SIZE),
+              space: MINT_SIZE,
+              programId: TOKEN_PROGRAM_ID,
+            }),
+            createInitializeMintInstruction(mintKeypair.publicKey, 6, wallet.publicKey),
+          ),
+          [mintKeypair],
+        );
+        await program.methods
+          .initializeConfig({
+            relayer: relayerKeypair.publicKey,
+            tokenMint: mintKeypair.publicKey,
+            feeBps: 50,
+            claimAuthority: wallet.publicKey,
+          })
+          .accountsPartial({
+            upgradeAuthority: wallet.publicKey,
+            program: program.programId,
+            programData: PublicKey.findProgramAddressSync(
+              [program.programId.toBuffer()],
+              BPF_LOADER_UPGRADEABLE_ID,
+            )[0],
+            config: configPda,
+          })
+          .rpc();
+      }
+
+      const config = await program.account.config.fetch(configPda);
+      tokenMint = config.tokenMint;
+      feeBps = config.feeBps;
+      feeVaultTokenAccount = await createTokenAccount(
+        provider,
+        wallet.publicKey,
+        tokenMint,
+        feeVault,
+      );
+
+      await program.methods
+        .setRelayer(relayerKeypair.publicKey)
+        .accountsPartial({ config: configPda, upgradeAuthority: wallet.publicKey })
+        .rpc();
+      if (!config.claimAuthority.equals(wallet.publicKey)) {
+        await program.methods
+          .setClaimAuthority(wallet.publicKey)
+          .accountsPartial({ config: configPda, upgradeAuthority: wallet.publicKey })
+          .rpc();
+      }
+      await program.methods
+        .setRateLimit(new BN(0), new BN(0))
+        .accountsPartial({ config: configPda, upgradeAuthority: wallet.publicKey })
+        .rpc();
+      await program.methods
+        .setTipDisputeWindow(new BN(0))
+        .accountsPartial({ config: configPda, upgradeAuthority: wallet.publicKey })
+        .rpc();
+    });
+
+    it('enforces the sliding sender nonce window', async () => {
+      const sender = await claimTippingVault();
+      const recipient = await createRecipient();
+
+      await sendTip(sender, recipient, { nonce: 100 });
+      await expectError(sendTip(sender, recipient, { nonce: 100 }), 'DuplicateSenderNonce');
+
+      await sendTip(sender, recipient, { nonce: 90 });
+      await expectError(sendTip(sender, recipient, { nonce: 90 }), 'DuplicateSenderNonce');
+
+      await sendTip(sender, recipient, { nonce: 37 });
+      await expectError(sendTip(sender, recipient, { nonce: 36 }), 'StaleSenderNonce');
+
+      // A jump of 64 or more clears the whole bitmap.
+      await sendTip(sender, recipient, { nonce: 200 });
+      await sendTip(sender, recipient, { nonce: 137 });
+      await expectError(sendTip(sender, recipient, { nonce: 136 }), 'StaleSenderNonce');
+      await expectError(sendTip(sender, recipient, { nonce: 200 }), 'DuplicateSenderNonce');
+
+      const allowance = await program.account.allowance.fetch(allowanceFor(sender));
+      assert.strictEqual(allowance.senderNonces.highest.toString(), '200');
+
+      const recipientBalance = await connection.getTokenAccountBalance(
+        recipient.vaultTokenAccount,
+      );
+      assert.strictEqual(recipientBalance.value.amount, '50000');
+    });
+
+    it('requires an ed25519 tip intent signed by the vault authority when enabled', async () => {
+      const sender = await claimTippingVault();
+      const recipient = await createRecipient();
+
+      await program.methods
+        .setTipSignatureRequired(true)
+        .accountsPartial({ authority: wallet.publicKey, vault: sender.vault })
+        .rpc();
+
+      const amount = new BN(25_000);
+      const tipId = randomBytes(32);
+      const nonce = 1;
+      const validUntil = new BN(Math.floor(Date.now() / 1000) + 600);
+
+      const intentMessage = (signedAmount: BN, signedValidUntil: BN): Buffer =>
+        Buffer.concat([
+          Buffer.from('tipcoin:tip_intent:v1'),
+          sender.vault.toBuffer(),
+          relayerKeypair.publicKey.toBuffer(),
+          recipient.hashedUserId,
+          signedAmount.toArrayLike(Buffer, 'le', 8),
+          tipId,
+          new BN(nonce).toArrayLike(Buffer, 'le', 8),
+          signedValidUntil.toArrayLike(Buffer, 'le', 8),
+        ]);
+      const signIntent = (signer: Keypair, message: Buffer): TransactionInstruction =>
+        anchor.web3.Ed25519Program.createInstructionWithPrivateKey({
+          privateKey: signer.secretKey,
+          message,
+        });
+      const tipWithIntent = (
+        signatureInstruction: TransactionInstruction | null,
+        tipValidUntil: BN = validUntil,
+      ) =>
+        sendTip(sender, recipient, {
+          amount,
+          nonce,
+          tipId,
+          validUntil: tipValidUntil,
+          instructionsSysvar: anchor.web3.SYSVAR_INSTRUCTIONS_PUBKEY,
+          preInstructions: signatureInstruction ? [signatureInstruction] : [],
+        });
+
+      await expectError(
+        sendTip(sender, recipient, { amount, nonce, tipId, validUntil }),
+        'MissingTipSignature',
+      );
+      await expectError(tipWithIntent(null), 'InvalidSignatureInstruction');
+      await expectError(
+        tipWithIntent(signIntent(wallet.payer, intentMessage(amount, new BN(0))), new BN(0)),
+        'MissingIntentDeadline',
+      );
+      await expectError(
+        tipWithIntent(signIntent(Keypair.generate(), intentMessage(amount, validUntil))),
+        'InvalidSignatureInstruction',
+      );
+      await expectError(
+        tipWithIntent(signIntent(wallet.payer, intentMessage(amount.addn(1), validUntil))),
+        'IntentMismatch',
+      );
+
+      await tipWithIntent(signIntent(wallet.payer, intentMessage(amount, validUntil)));
+
+      const recipientBalance = await connection.getTokenAccountBalance(
+        recipient.vaultTokenAccount,
+      );
+      assert.strictEqual(recipientBalance.value.amount, amount.toString());
+    });
+
+    it('rate limits tips with a per-vault token bucket', async () => {
+      const sender = await claimTippingVault();
+      const recipient = await createRecipient();
+
+      await program.methods
+        .setRateLimit(new BN(2), new BN(60 * 60))
+        .accountsPartial({ config: configPda, upgradeAuthority: wallet.publicKey })
+        .rpc();
+
+      try {
+        await sendTip(sender, recipient, { nonce: 1 });
+        await sendTip(sender, recipient, { nonce: 2 });
+        await expectError(sendTip(sender, recipient, { nonce: 3 }), 'RateLimited');
+
+        let vaultAccount = await program.account.vault.fetch(sender.vault);
+        assert.ok(
+          vaultAccount.tipBucketTokens.ltn(60 * 60),
+          'Bucket should hold less than one tip after the limit is hit',
+        );
+
+        await program.methods
+          .setVaultRateLimit(true, new BN(0), new BN(0))
+          .accountsPartial({
+            config: configPda,
+            upgradeAuthority: wallet.publicKey,
+            vault: sender.vault,
+          })
+          .rpc();
+        await sendTip(sender, recipient, { nonce: 3 });
+
+        await program.methods
+          .setVaultRateLimit(false, new BN(0), new BN(0))
+          .accountsPartial({
+            config: configPda,
+            upgradeAuthority: wallet.publicKey,
+            vault: sender.vault,
+          })
+          .rpc();
+        vaultAccount = await program.account.vault.fetch(sender.vault);
+        assert.strictEqual(vaultAccount.rateLimitOverride, false);
+      } finally {
+        await program.methods
+          .setRateLimit(new BN(0), new BN(0))
+          .accountsPartial({ config: configPda, upgradeAuthority: wallet.publicKey })
+          .rpc();
+      }
+    });
+
+    it('holds escrowed tips until they are finalized or reversed', async () => {
+      const sender = await claimTippingVault();
+      const recipient = await createRecipient();
+      const disputeWindowSeconds = 5;
+
+      const tipEscrow = findPda(Buffer.from('tip_escrow'), configPda.toBuffer());
+      const tipEscrowTokenAccount = await createTokenAccount(
+        provider,
+        wallet.publicKey,
+        tokenMint,
+        tipEscrow,
+      );
+
+      const escrowTip = (tipId: Buffer, nonce: number, amount: BN) =>
+        program.methods
+          .escrowTip(
+            amount,
+            Array.from(tipId) as number[],
+            new BN(nonce),
+            null,
+            Array.from(recipient.hashedUserId) as number[],
+            new BN(0),
+          )
+          .accountsPartial({
+            config: configPda,
+            relayer: relayerKeypair.publicKey,
+            senderVault: sender.vault,
+            senderAllowance: allowanceFor(sender),
+            recipientRules: null,
+            recipientVault: recipient.vault,
+            feeVault,
+            tipEscrow,
+            pendingTip: findPda(Buffer.from('pending_tip'), sender.vault.toBuffer(), tipId),
+            tipReceipt: findPda(Buffer.from('tip_receipt'), sender.vault.toBuffer(), tipId),
+            senderVaultTokenAccount: sender.vaultTokenAccount,
+            tipEscrowTokenAccount,
+            instructionsSysvar: null,
+            tokenProgram: TOKEN_PROGRAM_ID,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([relayerKeypair])
+          .rpc();
+      const finalizeTip = (pendingTip: PublicKey) =>
+        program.methods
+          .finalizeTip()
+          .accountsPartial({
+            config: configPda,
+            relayer: relayerKeypair.publicKey,
+            pendingTip,
+            tipEscrow,
+            tipEscrowTokenAccount,
+            recipientVaultTokenAccount: recipient.vaultTokenAccount,
+            feeVault,
+            feeVaultTokenAccount,
+            tokenProgram: TOKEN_PROGRAM_ID,
+          })
+          .rpc();
+      const reverseTip = (pendingTip: PublicKey, authority: Keypair) =>
+        program.methods
+          .reverseTip()
+          .accountsPartial({
+            config: configPda,
+            authority: authority.publicKey,
+            relayer: relayerKeypair.publicKey,
+            pendingTip,
+            senderVault: sender.vault,
+            senderAllowance: allowanceFor(sender),
+            tipEscrow,
+            tipEscrowTokenAccount,
+            senderVaultTokenAccount: sender.vaultTokenAccount,
+            tokenProgram: TOKEN_PROGRAM_ID,
+          })
+          .signers([authority])
+          .rpc();
+
+      await program.methods
+        .setTipDisputeWindow(new BN(disputeWindowSeconds))
+        .accountsPartial({ config: configPda, upgradeAuthority: wallet.publicKey })
+        .rpc();
+      await program.methods
+        .setVaultRateLimit(true, new BN(3), new BN(3_600))
+        .accountsPartial({
+          config: configPda,
+          upgradeAuthority: wallet.publicKey,
+          vault: sender.vault,
+        })
+        .rpc();
+
+      try {
+        await expectError(sendTip(sender, recipient, { nonce: 1 }), 'TipEscrowRequired');
+
+        const walletAmount = new BN(10_000);
+        const walletTokenAccount = await createTokenAccount(
+          provider,
+          wallet.publicKey,
+          tokenMint,
+          Keypair.generate().publicKey,
+        );
+        await tipToWallet(sender, relayerKeypair, walletTokenAccount, walletAmount, 3);
+        assert.strictEqual(await tokenBalance(walletTokenAccount), walletAmount.toString());
+
+        const finalizedAmount = new BN(40_000);
+        const finalizedTipId = randomBytes(32);
+        const finalizedPendingTip = findPda(
+          Buffer.from('pending_tip'),
+          sender.vault.toBuffer(),
+          finalizedTipId,
+        );
+        await escrowTip(finalizedTipId, 1, finalizedAmount);
+        await expectError(finalizeTip(finalizedPendingTip), 'TipDisputeWindowOpen');
+
+        const reversedAmount = new BN(60_000);
+        const reversedTipId = randomBytes(32);
+        const reversedPendingTip = findPda(
+          Buffer.from('pending_tip'),
+          sender.vault.toBuffer(),
+          reversedTipId,
+        );
+        await escrowTip(reversedTipId, 2, reversedAmount);
+        await expectError(reverseTip(reversedPendingTip, Keypair.generate()), 'InvalidAuthority');
+
+        const vaultBeforeReverse = await program.account.vault.fetch(sender.vault);
+        assert.ok(vaultBeforeReverse.tipBucketTokens.ltn(3_600));
+
+        const senderBalanceBeforeReverse = await connection.getTokenAccountBalance(
+          sender.vaultTokenAccount,
+        );
+        await reverseTip(reversedPendingTip, relayerKeypair);
+        const senderBalanceAfterReverse = await connection.getTokenAccountBalance(
+          sender.vaultTokenAccount,
+        );
+        assert.strictEqual(
+          (
+            BigInt(senderBalanceAfterReverse.value.amount) -
+            BigInt(senderBalanceBeforeReverse.value.amount)
+          ).toString(),
+          reversedAmount.add(feeFor(reversedAmount)).toString(),
+        );
+        assert.strictEqual(await connection.getAccountInfo(reversedPendingTip), null);
+
+        const spent = finalizedAmount
+          .add(feeFor(finalizedAmount))
+          .add(walletAmount)
+          .add(feeFor(walletAmount));
+        const allowance = await program.account.allowance.fetch(allowanceFor(sender));
+        assert.strictEqual(allowance.remaining.toString(), new BN(1_000_000).sub(spent).toString());
+        const vaultAfterReverse = await program.account.vault.fetch(sender.vault);
+        assert.ok(vaultAfterReverse.tipBucketTokens.gten(3_600));
+        assert.strictEqual(vaultAfterReverse.withdrawnInWindow.toString(), spent.toString());
+
+        await sleep((disputeWindowSeconds + 2) * 1000);
+        await finalizeTip(finalizedPendingTip);
+
+        const recipientBalance = await connection.getTokenAccountBalance(
+          recipient.vaultTokenAccount,
+        );
+        assert.strictEqual(recipientBalance.value.amount, finalizedAmount.toString());
+        assert.strictEqual(await connection.getAccountInfo(finalizedPendingTip), null);
+      } finally {
+        await program.methods
+          .setTipDisputeWindow(new BN(0))
+          .accountsPartial({ config: configPda, upgradeAuthority: wallet.publicKey })
+          .rpc();
+        await program.methods
+          .setVaultRateLimit(false, new BN(0), new BN(0))
+          .accountsPartial({
+            config: configPda,
+            upgradeAuthority: wallet.publicKey,
+            vault: sender.vault,
+          })
+          .rpc();
+      }
+    });
+
+    it('charges bounties to the daily withdrawal limit and credits cancellations', async () => {
+      const { vault, vaultTokenAccount } = await claimFundedVault(1_000_000n);
+      const dailyLimit = new BN(300_000);
+
+      await program.methods
+        .setWithdrawLimit(dailyLimit)
+        .accountsPartial({ authority: wallet.publicKey, vault })
+        .rpc();
+
+      const createBounty = (bountyId: Buffer, amount: BN) => {
+        const bounty = findPda(Buffer.from('bounty'), vault.toBuffer(), bountyId);
+        return program.methods
+          .createBounty(Array.from(bountyId) as number[], amount)
+          .accountsPartial({
+            config: configPda,
+            authority: wallet.publicKey,
+            creatorVault: vault,
+            creatorVaultTokenAccount: vaultTokenAccount,
+            bounty,
+            bountyTokenAccount: findPda(Buffer.from('bounty_token'), bounty.toBuffer()),
+            tokenMint,
+            tokenProgram: TOKEN_PROGRAM_ID,
+            systemProgram: SystemProgram.programId,
+          })
+          .rpc();
+      };
+
+      await expectError(
+        createBounty(Buffer.alloc(32, 1), dailyLimit.addn(1)),
+        'WithdrawLimitExceeded',
+      );
+
+      await createBounty(Buffer.alloc(32, 2), new BN(200_000));
+      let vaultAccount = await program.account.vault.fetch(vault);
+      assert.strictEqual(vaultAccount.withdrawnInWindow.toString(), '200000');
+
+      await expectError(
+        createBounty(Buffer.alloc(32, 3), new BN(100_001)),
+        'WithdrawLimitExceeded',
+      );
+
+      await createBounty(Buffer.alloc(32, 4), new BN(100_000));
+      vaultAccount = await program.account.vault.fetch(vault);
+      assert.strictEqual(vaultAccount.withdrawnInWindow.toString(), dailyLimit.toString());
+
+      const vaultBalance = await connection.getTokenAccountBalance(vaultTokenAccount);
+      assert.strictEqual(vaultBalance.value.amount, '700000');
+
+      const cancelledBounty = findPda(Buffer.from('bounty'), vault.toBuffer(), Buffer.alloc(32, 2));
+      const cancelBounty = (creator: Keypair) =>
+        program.methods
+          .cancelBounty()
+          .accountsPartial({
+            creator: creator.publicKey,
+            bounty: cancelledBounty,
+            bountyTokenAccount: findPda(Buffer.from('bounty_token'), cancelledBounty.toBuffer()),
+            creatorVault: vault,
+            creatorVaultTokenAccount: vaultTokenAccount,
+            tokenProgram: TOKEN_PROGRAM_ID,
+          })
+          .signers([creator])
+          .rpc();
+
+      await expectError(cancelBounty(Keypair.generate()), 'InvalidAuthority');
+      await cancelBounty(wallet.payer);
+      vaultAccount = await program.account.vault.fetch(vault);
+      assert.strictEqual(vaultAccount.withdrawnInWindow.toString(), '100000');
+      assert.strictEqual(await tokenBalance(vaultTokenAccount), '900000');
+
+      await createBounty(Buffer.alloc(32, 5), new BN(200_000));
+    });
+
+    it('executes a group withdrawal only once the M-of-N threshold is met', async () => {
+      const [memberTwo, memberThree, outsider] = [
+        Keypair.generate(),
+        Keypair.generate(),
+        Keypair.generate(),
+      ];
+      for (const keypair of [memberTwo, memberThree, outsider]) {
+        await connection.confirmTransaction(
+          await connection.requestAirdrop(keypair.publicKey, anchor.web3.LAMPORTS_PER_SOL),
+          'confirmed',
+        );
+      }
+
+      const groupId = createHash('sha256').update(`group-${Date.now()}-${Math.random()}`).digest();
+      const groupVault = findPda(Buffer.from('group_vault'), groupId);
+      const vault = findPda(Buffer.from('vault'), groupVault.toBuffer());
+
+      await program.methods
+        .createGroupVault(
+          Array.from(groupId) as number[],
+          [wallet.publicKey, memberTwo.publicKey, memberThree.publicKey],
+          2,
+        )
+        .accountsPartial({
+          creator: wallet.publicKey,
+          claimAuthority: wallet.publicKey,
+          config: configPda,
+          groupVault,
+          vault,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+
+      const vaultTokenAccount = await createTokenAccount(
+        provider,
+        wallet.publicKey,
+        tokenMint,
+        vault,
+      );
+      await provider.sendAndConfirm(
+        new Transaction().add(
+          createMintToInstruction(tokenMint, vaultTokenAccount, wallet.publicKey, 500_000n),
+        ),
+      );
+      const destinationTokenAccount = await createTokenAccount(
+        provider,
+        wallet.publicKey,
+        tokenMint,
+        wallet.publicKey,
+      );
+
+      const proposal = findPda(
+        Buffer.from('group_proposal'),
+        groupVault.toBuffer(),
+        new BN(0).toArrayLike(Buffer, 'le', 8),
+      );
+      await program.methods
+        .proposeGroupAction({
+          withdraw: { destination: destinationTokenAccount, amount: new BN(200_000) },
+        })
+        .accountsPartial({ proposer: wallet.publicKey, groupVault, proposal })
+        .rpc();
+
+      const approve = (member: Keypair) =>
+        program.methods
+          .approveGroupProposal()
+          .accountsPartial({
+            member: member.publicKey,
+            groupVault,
+            proposal,
+            approval: findPda(
+              Buffer.from('group_approval'),
+              proposal.toBuffer(),
+              member.publicKey.toBuffer(),
+            ),
+          })
+          .signers([member])
+          .rpc();
+      const execute = () =>
+        program.methods
+          .executeGroupProposal()
+          .accountsPartial({
+            config: configPda,
+            member: memberThree.publicKey,
+            proposer: wallet.publicKey,
+            groupVault,
+            proposal,
+            vault,
+            vaultTokenAccount,
+            destinationTokenAccount,
+            tokenProgram: TOKEN_PROGRAM_ID,
+          })
+          .signers([memberThree])
+          .rpc();
+
+      await expectError(approve(outsider), 'NotGroupMember');
+
+      await approve(memberTwo);
+      await expectError(execute(), 'GroupProposalNotApproved');
+      await assert.rejects(approve(memberTwo));
+
+      await program.methods
+        .approveGroupProposal()
+        .accountsPartial({
+          member: wallet.publicKey,
+          groupVault,
+          proposal,
+          approval: findPda(
+            Buffer.from('group_approval'),
+            proposal.toBuffer(),
+            wallet.publicKey.toBuffer(),
+          ),
+        })
+        .rpc();
+      await execute();
+
+      const vaultBalance = await connection.getTokenAccountBalance(vaultTokenAccount);
+      const destinationBalance = await connection.getTokenAccountBalance(destinationTokenAccount);
+      assert.strictEqual(vaultBalance.value.amount, '300000');
+      assert.strictEqual(destinationBalance.value.amount, '200000');
+      assert.strictEqual(await connection.getAccountInfo(proposal), null);
+
+      const vaultAccount = await program.account.vault.fetch(vault);
+      assert.strictEqual(vaultAccount.authority.toBase58(), groupVault.toBase58());
+      assert.strictEqual(vaultAccount.withdrawnInWindow.toString(), '200000');
+      assert.strictEqual(vaultAccount.tipBucketUpdatedAt.toString(), '0');
+    });
+
+    it('lets a third-party wallet fund an unclaimed vault with deposit_to', async () => {
+      const funder = Keypair.generate();
+      await airdrop(funder.publicKey);
+      const funderTokenAccount = await createFundedTokenAccount(funder.publicKey, 500_000n);
+      const strangerTokenAccount = await createFundedTokenAccount(wallet.publicKey, 500_000n);
+      const recipient = await createRecipient();
+
+      const depositTo = (amount: BN, fromTokenAccount: PublicKey) =>
+        program.methods
+          .depositTo(Array.from(recipient.hashedUserId) as number[], amount)
+          .accountsPartial({
+            config: configPda,
+            funder: funder.publicKey,
+            vault: recipient.vault,
+            funderTokenAccount: fromTokenAccount,
+            vaultTokenAccount: recipient.vaultTokenAccount,
+            tokenProgram: TOKEN_PROGRAM_ID,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([funder])
+          .rpc();
+
+      await expectError(depositTo(new BN(0), funderTokenAccount), 'InvalidDepositAmount');
+      await expectError(depositTo(new BN(100_000), strangerTokenAccount), 'InvalidAuthority');
+
+      const depositEvents: Array<{ funder: PublicKey; vault: PublicKey; amount: BN }> = [];
+      const listener = program.addEventListener('depositEvent', (event) => {
+        depositEvents.push(event as unknown as { funder: PublicKey; vault: PublicKey; amount: BN });
+      });
+      try {
+        await depositTo(new BN(300_000), funderTokenAccount);
+      } finally {
+        await program.removeEventListener(listener);
+      }
+
+      const vaultAccount = await program.account.vault.fetch(recipient.vault);
+      assert.strictEqual(vaultAccount.claimed, false);
+      assert.ok(vaultAccount.authority.equals(findPda(Buffer.from('unclaimed_authority'))));
+      assert.strictEqual(await tokenBalance(recipient.vaultTokenAccount), '300000');
+      assert.strictEqual(await tokenBalance(funderTokenAccount), '200000');
+
+      const event = depositEvents.find((record) => record.vault.equals(recipient.vault));
+      assert.ok(event, 'DepositEvent not emitted');
+      assert.ok(event!.funder.equals(funder.publicKey));
+      assert.strictEqual(event!.amount.toString(), '300000');
+    });
+
+    it('withdraws to a fresh wallet and sweeps the vault with withdraw_all', async () => {
+      const vault = await claimFundedVault(400_000n);
+      const destination = Keypair.generate().publicKey;
+      const destinationTokenAccount = associatedTokenAddress(destination);
+      const walletAccounts = {
+        config: configPda,
+        authority: wallet.publicKey,
+        vault: vault.vault,
+        vaultTokenAccount: vault.vaultTokenAccount,
+        destination,
+        destinationTokenAccount,
+        tokenMint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      };
+
+      await expectError(
+        program.methods.withdrawToWallet(new BN(0)).accountsPartial(walletAccounts).rpc(),
+        'InvalidWithdrawAmount',
+      );
+      const intruder = Keypair.generate();
+      await airdrop(intruder.publicKey);
+      await expectError(
+        program.methods
+          .withdrawToWallet(new BN(1_000))
+          .accountsPartial({ ...walletAccounts, authority: intruder.publicKey })
+          .signers([intruder])
+          .rpc(),
+        'ConstraintHasOne',
+      );
+
+      await program.methods.withdrawToWallet(new BN(150_000)).accountsPartial(walletAccounts).rpc();
+      assert.strictEqual(await tokenBalance(destinationTokenAccount), '150000');
+
+      await program.methods.withdrawAllToWallet().accountsPartial(walletAccounts).rpc();
+      assert.strictEqual(await tokenBalance(destinationTokenAccount), '400000');
+      assert.strictEqual(await tokenBalance(vault.vaultTokenAccount), '0');
+
+      const withdrawAll = () =>
+        program.methods
+          .withdrawAll()
+          .accountsPartial({
+            config: configPda,
+            authority: wallet.publicKey,
+            vault: vault.vault,
+            vaultTokenAccount: vault.vaultTokenAccount,
+            destinationTokenAccount,
+            tokenProgram: TOKEN_PROGRAM_ID,
+          })
+          .rpc();
+      await expectError(withdrawAll(), 'InsufficientVaultBalance');
+    });
+
+    it('relays a signed withdraw intent and pays the relayer fee', async () => {
+      const vault = await claimFundedVault(300_000n);
+      const destination = Keypair.generate().publicKey;
+      const destinationTokenAccount = associatedTokenAddress(destination);
+      const relayerTokenAccount = await createTokenAccount(
+        provider,
+        wallet.publicKey,
+        tokenMint,
+        relayerKeypair.publicKey,
+      );
+
+      type Intent = { amount: BN; relayerFee: BN; nonce: BN; expiresAt: BN };
+      const intentMessage = (intent: Intent): Buffer =>
+        Buffer.concat([
+          Buffer.from('tipcoin:withdraw_intent:v1'),
+          vault.vault.toBuffer(),
+          destination.toBuffer(),
+          intent.amount.toArrayLike(Buffer, 'le', 8),
+          intent.relayerFee.toArrayLike(Buffer, 'le', 8),
+          intent.nonce.toArrayLike(Buffer, 'le', 8),
+          intent.expiresAt.toArrayLike(Buffer, 'le', 8),
+        ]);
+      const relayedWithdraw = (intent: Intent, signed: Intent = intent) =>
+        program.methods
+          .relayedWithdraw({ vault: vault.vault, destination, ...intent })
+          .accountsPartial({
+            config: configPda,
+            relayer: relayerKeypair.publicKey,
+            vault: vault.vault,
+            vaultTokenAccount: vault.vaultTokenAccount,
+            destination,
+            destinationTokenAccount,
+            relayerTokenAccount,
+            tokenMint,
+            instructionsSysvar: anchor.web3.SYSVAR_INSTRUCTIONS_PUBKEY,
+            tokenProgram: TOKEN_PROGRAM_ID,
+            associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+            systemProgram: SystemProgram.programId,
+          })
+          .preInstructions([
+            anchor.web3.Ed25519Program.createInstructionWithPrivateKey({
+              privateKey: wallet.payer.secretKey,
+              message: intentMessage(signed),
+            }),
+          ])
+          .signers([relayerKeypair])
+          .rpc();
+
+      const expiresAt = new BN(Math.floor(Date.now() / 1000) + 600);
+      const intent: Intent = {
+        amount: new BN(100_000),
+        relayerFee: new BN(2_000),
+        nonce: new BN(1),
+        expiresAt,
+      };
+
+      await expectError(
+        relayedWithdraw(intent, { ...intent, amount: new BN(99_000) }),
+        'IntentMismatch',
+      );
+      await expectError(
+        relayedWithdraw({ ...intent, expiresAt: new BN(Math.floor(Date.now() / 1000) - 600) }),
+        'IntentExpired',
+      );
+
+      await relayedWithdraw(intent);
+      assert.strictEqual(await tokenBalance(destinationTokenAccount), '100000');
+      assert.strictEqual(await tokenBalance(relayerTokenAccount), '2000');
+      assert.strictEqual(await tokenBalance(vault.vaultTokenAccount), '198000');
+
+      await expectError(relayedWithdraw(intent), 'InvalidIntentNonce');
+    });
+
+    it('adjusts an allowance by a delta with an optional compare-and-swap guard', async () => {
+      const sender = await claimTippingVault();
+      const allowance = allowanceFor(sender);
+      const adjust = (delta: { increase: [BN] } | { decrease: [BN] }, expected: BN | null) =>
+        program.methods
+          .adjustAllowance(delta, expected)
+          .accountsPartial({ authority: wallet.publicKey, allowance })
+          .rpc();
+
+      await adjust({ increase: [new BN(5_000)] }, new BN(1_000_000));
+      let allowanceAccount = await program.account.allowance.fetch(allowance);
+      assert.strictEqual(allowanceAccount.cap.toString(), '1005000');
+      assert.strictEqual(allowanceAccount.remaining.toString(), '1005000');
+
+      await expectError(
+        adjust({ increase: [new BN(5_000)] }, new BN(1_000_000)),
+        'AllowanceRemainingMismatch',
+      );
+      await expectError(adjust({ decrease: [new BN(0)] }, null), 'InvalidAllowanceDelta');
+
+      await adjust({ decrease: [new BN(105_000)] }, null);
+      allowanceAccount = await program.account.allowance.fetch(allowance);
+      assert.strictEqual(allowanceAccount.cap.toString(), '900000');
+      assert.strictEqual(allowanceAccount.remaining.toString(), '900000');
+    });
+
+    it('keeps a separate allowance per spender', async () => {
+      const sender = await claimTippingVault();
+      const recipient = await createRecipient();
+      const bot = Keypair.generate();
+      await airdrop(bot.publicKey);
+      const botAllowance = findPda(
+        Buffer.from('allowance'),
+        sender.hashedUserId,
+        bot.publicKey.toBuffer(),
+      );
+
+      await program.methods
+        .approveAllowance({
+          spender: bot.publicKey,
+          amount: new BN(20_000),
+          periodSeconds: new BN(0),
+          expiresAt: new BN(0),
+          maxPerTip: new BN(0),
+          validUntil: new BN(0),
+        })
+        .accountsPartial({
+          authority: wallet.publicKey,
+          vault: sender.vault,
+          allowance: botAllowance,
+        })
+        .rpc();
+
+      const amount = new BN(15_000);
+      await sendTip(sender, recipient, { amount, nonce: 1, spender: bot });
+
+      const botAllowanceAccount = await program.account.allowance.fetch(botAllowance);
+      assert.ok(botAllowanceAccount.spender.equals(bot.publicKey));
+      assert.strictEqual(
+        botAllowanceAccount.remaining.toString(),
+        new BN(20_000).sub(amount.add(feeFor(amount))).toString(),
+      );
+      const relayerAllowanceAccount = await program.account.allowance.fetch(allowanceFor(sender));
+      assert.strictEqual(relayerAllowanceAccount.remaining.toString(), '1000000');
+
+      await expectError(
+        sendTip(sender, recipient, { amount, nonce: 2, spender: bot }),
+        'AllowanceExceeded',
+      );
+      await expectError(
+        sendTip(sender, recipient, { amount, nonce: 1, spender: Keypair.generate() }),
+        'AccountNotInitialized',
+      );
+    });
+
+    it('carries relayer allowances over a set_relayer rotation', async () => {
+      const sender = await claimTippingVault();
+      const nextRelayer = Keypair.generate();
+      await airdrop(nextRelayer.publicKey);
+      const nextAllowance = findPda(
+        Buffer.from('allowance'),
+        sender.hashedUserId,
+        nextRelayer.publicKey.toBuffer(),
+      );
+      const setRelayer = (relayer: PublicKey) =>
+        program.methods
+          .setRelayer(relayer)
+          .accountsPartial({ config: configPda, upgradeAuthority: wallet.publicKey })
+          .rpc();
+      const migrate = (relayer: Keypair) =>
+        program.methods
+          .migrateRelayerAllowance()
+          .accountsPartial({
+            config: configPda,
+            relayer: relayer.publicKey,
+            authority: wallet.publicKey,
+            previousAllowance: allowanceFor(sender),
+            allowance: nextAllowance,
+            previousRecipientRules: null,
+            recipientRules: null,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([relayer])
+          .rpc();
+
+      await setRelayer(nextRelayer.publicKey);
+      try {
+        const config = await program.account.config.fetch(configPda);
+        assert.ok(config.previousRelayer.equals(relayerKeypair.publicKey));
+
+        await expectError(migrate(relayerKeypair), 'InvalidRelayer');
+        await migrate(nextRelayer);
+      } finally {
+        await setRelayer(relayerKeypair.publicKey);
+      }
+
+      const migrated = await program.account.allowance.fetch(nextAllowance);
+      assert.ok(migrated.spender.equals(nextRelayer.publicKey));
+      assert.ok(migrated.authority.equals(wallet.publicKey));
+      assert.strictEqual(migrated.cap.toString(), '1000000');
+      assert.strictEqual(migrated.remaining.toString(), '1000000');
+      assert.strictEqual(await connection.getAccountInfo(allowanceFor(sender)), null);
+    });
+
+    it('enforces recipient allowlists attached to an allowance', async () => {
+      const sender = await claimTippingVault();
+      const allowed = await createRecipient();
+      const other = await createRecipient();
+      const allowance = allowanceFor(sender);
+      const recipientRules = findPda(Buffer.from('recipient_rules'), allowance.toBuffer());
+      type RuleMode = { allowlist: Record<string, never> } | { blocklist: Record<string, never> };
+      const addRule = (mode: RuleMode) =>
+        program.methods
+          .addRecipientRule(mode, Array.from(allowed.hashedUserId) as number[])
+          .accountsPartial({
+            authority: wallet.publicKey,
+            allowance,
+            recipientRules,
+            systemProgram: SystemProgram.programId,
+          })
+          .rpc();
+
+      await addRule({ allowlist: {} });
+      await expectError(addRule({ blocklist: {} }), 'RecipientRuleModeMismatch');
+
+      await sendTip(sender, allowed, { nonce: 1, recipientRules });
+      await expectError(
+        sendTip(sender, other, { nonce: 2, recipientRules }),
+        'RecipientNotAllowed',
+      );
+      await expectError(sendTip(sender, allowed, { nonce: 3 }), 'MissingRecipientRules');
+
+      const rules = await program.account.recipientRules.fetch(recipientRules);
+      assert.ok(rules.allowance.equals(allowance));
+      assert.deepStrictEqual(rules.recipients, [Array.from(allowed.hashedUserId)]);
+      assert.strictEqual(await tokenBalance(allowed.vaultTokenAccount), '10000');
+    });
+
+    it('lets a registered session key tip within its own budget', async () => {
+      const sender = await claimFundedVault(200_000n);
+      const recipient = await createRecipient();
+      const sessionKey = Keypair.generate();
+      await airdrop(sessionKey.publicKey);
+      const session = findPda(
+        Buffer.from('session'),
+        sender.vault.toBuffer(),
+        sessionKey.publicKey.toBuffer(),
+      );
+      const now = Math.floor(Date.now() / 1000);
+      const createSession = (expiresAt: number) =>
+        program.methods
+          .createSession(sessionKey.publicKey, new BN(50_000), new BN(expiresAt))
+          .accountsPartial({
+            authority: wallet.publicKey,
+            vault: sender.vault,
+            session,
+            systemProgram: SystemProgram.programId,
+          })
+          .rpc();
+      const tipWithSession = (amount: BN, nonce: number) => {
+        const tipId = randomBytes(32);
+        return program.methods
+          .tipWithSession(
+            amount,
+            Array.from(tipId) as number[],
+            new BN(nonce),
+            null,
+            Array.from(recipient.hashedUserId) as number[],
+            new BN(0),
+          )
+          .accountsPartial({
+            config: configPda,
+            sessionKey: sessionKey.publicKey,
+            senderVault: sender.vault,
+            session,
+            recipientVault: recipient.vault,
+            feeVault,
+            tipReceipt: findPda(Buffer.from('tip_receipt'), sender.vault.toBuffer(), tipId),
+            senderVaultTokenAccount: sender.vaultTokenAccount,
+            recipientVaultTokenAccount: recipient.vaultTokenAccount,
+            feeVaultTokenAccount,
+            tokenProgram: TOKEN_PROGRAM_ID,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([sessionKey])
+          .rpc();
+      };
+
+      await expectError(createSession(now - 60), 'InvalidSessionExpiry');
+      await createSession(now + 600);
+
+      const amount = new BN(20_000);
+      await tipWithSession(amount, 1);
+      assert.strictEqual(await tokenBalance(recipient.vaultTokenAccount), '20000');
+      const sessionAccount = await program.account.session.fetch(session);
+      assert.strictEqual(
+        sessionAccount.remaining.toString(),
+        new BN(50_000).sub(amount.add(feeFor(amount))).toString(),
+      );
+
+      await expectError(tipWithSession(new BN(30_000), 2), 'SessionBudgetExceeded');
+
+      await program.methods
+        .revokeSession()
+        .accountsPartial({ authority: wallet.publicKey, vault: sender.vault, session })
+        .rpc();
+      await expectError(tipWithSession(new BN(1_000), 3), 'AccountNotInitialized');
+    });
+
+    it('rains tips on several recipients with tip_many', async () => {
+      const sender = await claimTippingVault();
+      const recipients = [
+        await createRecipient(),
+        await createRecipient(),
+        await createRecipient(),
+      ];
+      const amounts = [new BN(10_000), new BN(20_000), new BN(30_000)];
+      const entries = recipients.map((recipient, index) => ({
+        recipientHashedUserId: Array.from(recipient.hashedUserId) as number[],
+        amount: amounts[index],
+        recipientVaultBump: PublicKey.findProgramAddressSync(
+          [Buffer.from('vault'), recipient.hashedUserId],
+          program.programId,
+        )[1],
+      }));
+      const tipMany = (batch: typeof entries, targets: VaultAccounts[], nonce: number) => {
+        const tipId = randomBytes(32);
+        return program.methods
+          .tipMany(batch, Array.from(tipId) as number[], new BN(nonce), null, new BN(0))
+          .accountsPartial({
+            config: configPda,
+            relayer: relayerKeypair.publicKey,
+            senderVault: sender.vault,
+            senderAllowance: allowanceFor(sender),
+            recipientRules: null,
+            feeVault,
+            tipReceipt: findPda(Buffer.from('tip_receipt'), sender.vault.toBuffer(), tipId),
+            senderVaultTokenAccount: sender.vaultTokenAccount,
+            feeVaultTokenAccount,
+            instructionsSysvar: null,
+            tokenProgram: TOKEN_PROGRAM_ID,
+            systemProgram: SystemProgram.programId,
+          })
+          .remainingAccounts(
+            targets.flatMap((target) => [
+              { pubkey: target.vault, isSigner: false, isWritable: true },
+              { pubkey: target.vaultTokenAccount, isSigner: false, isWritable: true },
+            ]),
+          )
+          .signers([relayerKeypair])
+          .rpc();
+      };
+
+      await expectError(
+        tipMany([entries[0], entries[0]], [recipients[0], recipients[0]], 1),
+        'DuplicateRecipient',
+      );
+      await expectError(tipMany(entries, recipients.slice(0, 2), 1), 'InvalidRecipientCount');
+
+      await tipMany(entries, recipients, 1);
+
+      for (const [index, recipient] of recipients.entries()) {
+        assert.strictEqual(
+          await tokenBalance(recipient.vaultTokenAccount),
+          amounts[index].toString(),
+        );
+      }
+      const batchTotal = amounts.reduce(
+        (sum, amount) => sum.add(amount).add(feeFor(amount)),
+        new BN(0),
+      );
+      const allowance = await program.account.allowance.fetch(allowanceFor(sender));
+      assert.strictEqual(
+        allowance.remaining.toString(),
+        new BN(1_000_000).sub(batchTotal).toString(),
+      );
+    });
+
+    it('rejects tips below the configured minimum amounts', async () => {
+      const sender = await claimTippingVault();
+      const recipient = await createRecipient();
+      const setMinTipAmounts = (minTip: number, minNewVaultTip: number, authority?: Keypair) =>
+        program.methods
+          .setMinTipAmounts(new BN(minTip), new BN(minNewVaultTip))
+          .accountsPartial({
+            config: configPda,
+            upgradeAuthority: (authority ?? wallet.payer).publicKey,
+          })
+          .signers(authority ? [authority] : [])
+          .rpc();
+
+      await expectError(setMinTipAmounts(5_000, 20_000, Keypair.generate()), 'InvalidAuthority');
+      await setMinTipAmounts(5_000, 20_000);
+      try {
+        await expectError(
+          sendTip(sender, recipient, { amount: new BN(4_000), nonce: 1 }),
+          'TipBelowMinimum',
+        );
+        await expectError(
+          sendTip(sender, recipient, { amount: new BN(10_000), nonce: 2 }),
+          'TipBelowMinimum',
+        );
+
+        await sendTip(sender, recipient, { amount: new BN(25_000), nonce: 3 });
+        await sendTip(sender, recipient, { amount: new BN(10_000), nonce: 4 });
+        assert.strictEqual(await tokenBalance(recipient.vaultTokenAccount), '35000');
+      } finally {
+        await setMinTipAmounts(0, 0);
+      }
+    });
+
+    it('tips a plain wallet token account with tip_to_wallet', async () => {
+      const sender = await claimTippingVault();
+      const destinationTokenAccount = await createTokenAccount(
+        provider,
+        wallet.publicKey,
+        tokenMint,
+        Keypair.generate().publicKey,
+      );
+
+      await tipToWallet(sender, relayerKeypair, destinationTokenAccount, new BN(30_000), 1);
+      assert.strictEqual(await tokenBalance(destinationTokenAccount), '30000');
+
+      const allowance = allowanceFor(sender);
+      await program.methods
+        .addRecipientRule({ allowlist: {} }, Array.from(randomBytes(32)) as number[])
+        .accountsPartial({
+          authority: wallet.publicKey,
+          allowance,
+          recipientRules: findPda(Buffer.from('recipient_rules'), allowance.toBuffer()),
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+      await expectError(
+        tipToWallet(sender, relayerKeypair, destinationTokenAccount, new BN(30_000), 2),
+        'WalletRecipientRestricted',
+      );
+    });
+
+    it('charges every allowance spend against the daily withdrawal limit', async () => {
+      const sender = await claimTippingVault();
+      const recipient = await createRecipient();
+      const destinationTokenAccount = await createTokenAccount(
+        provider,
+        wallet.publicKey,
+        tokenMint,
+        wallet.publicKey,
+      );
+
+      await approveSpender(sender, wallet.publicKey, new BN(1_000_000));
+      await program.methods
+        .setWithdrawLimit(new BN(50_000))
+        .accountsPartial({ authority: wallet.publicKey, vault: sender.vault })
+        .rpc();
+
+      const amount = new BN(40_000);
+      await tipToWallet(sender, wallet.payer, destinationTokenAccount, amount, 1);
+      const vaultAccount = await program.account.vault.fetch(sender.vault);
+      assert.strictEqual(
+        vaultAccount.withdrawnInWindow.toString(),
+        amount.add(feeFor(amount)).toString(),
+      );
+
+      await expectError(
+        tipToWallet(sender, wallet.payer, destinationTokenAccount, new BN(10_000), 2),
+        'WithdrawLimitExceeded',
+      );
+      await expectError(
+        sendTip(sender, recipient, { amount: new BN(10_000), nonce: 1 }),
+        'WithdrawLimitExceeded',
+      );
+    });
+
+    it('pays due subscription periods through the permissionless crank', async () => {
+      const sender = await claimFundedVault(200_000n);
+      const recipient = await createRecipient();
+      const subscription = findPda(
+        Buffer.from('subscription'),
+        sender.vault.toBuffer(),
+        recipient.hashedUserId,
+      );
+      const createSubscription = (intervalSeconds: number) =>
+        program.methods
+          .createSubscription(
+            Array.from(recipient.hashedUserId) as number[],
+            new BN(25_000),
+            new BN(intervalSeconds),
+            new BN(0),
+            new BN(2),
+          )
+          .accountsPartial({
+            authority: wallet.publicKey,
+            vault: sender.vault,
+            subscription,
+            systemProgram: SystemProgram.programId,
+          })
+          .rpc();
+      const crank = Keypair.generate();
+      await airdrop(crank.publicKey);
+      const executeSubscription = () =>
+        program.methods
+          .executeSubscription()
+          .accountsPartial({
+            config: configPda,
+            payer: crank.publicKey,
+            subscription,
+            senderVault: sender.vault,
+            recipientVault: recipient.vault,
+            feeVault,
+            senderVaultTokenAccount: sender.vaultTokenAccount,
+            recipientVaultTokenAccount: recipient.vaultTokenAccount,
+            feeVaultTokenAccount,
+            tokenProgram: TOKEN_PROGRAM_ID,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([crank])
+          .rpc();
+
+      await expectError(createSubscription(60), 'InvalidSubscriptionInterval');
+      await createSubscription(3_600);
+
+      await executeSubscription();
+      assert.strictEqual(await tokenBalance(recipient.vaultTokenAccount), '25000');
+      const subscriptionAccount = await program.account.subscription.fetch(subscription);
+      assert.strictEqual(subscriptionAccount.remainingPeriods.toString(), '1');
+      assert.ok(subscriptionAccount.nextDue.gtn(Math.floor(Date.now() / 1000) + 3_000));
+
+      await expectError(executeSubscription(), 'SubscriptionNotDue');
+
+      await program.methods
+        .cancelSubscription()
+        .accountsPartial({ authority: wallet.publicKey, subscription })
+        .rpc();
+      assert.strictEqual(await connection.getAccountInfo(subscription), null);
+    });
+
+    it('lets only the recipient side claim a funded pool', async () => {
+      const sender = await claimTippingVault();
+      const recipient = await createRecipient();
+      const targetAmount = new BN(50_000);
+      const { pool, poolTokenAccount } = await createPool(
+        recipient,
+        targetAmount,
+        Math.floor(Date.now() / 1000) + 600,
+      );
+      const poolContribution = findPda(
+        Buffer.from('pool_contribution'),
+        pool.toBuffer(),
+        sender.vault.toBuffer(),
+      );
+
+      await program.methods
+        .contributeToPool(targetAmount, new BN(1), new BN(0))
+        .accountsPartial({
+          config: configPda,
+          relayer: relayerKeypair.publicKey,
+          senderVault: sender.vault,
+          senderAllowance: allowanceFor(sender),
+          recipientRules: null,
+          pool,
+          poolContribution,
+          poolTokenAccount,
+          senderVaultTokenAccount: sender.vaultTokenAccount,
+          instructionsSysvar: null,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([relayerKeypair])
+        .rpc();
+      assert.strictEqual(
+        await tokenBalance(poolTokenAccount),
+        targetAmount.add(feeFor(targetAmount)).toString(),
+      );
+
+      const claimPool = (authority: Keypair) =>
+        program.methods
+          .claimPool()
+          .accountsPartial({
+            config: configPda,
+            payer: wallet.publicKey,
+            authority: authority.publicKey,
+            creator: wallet.publicKey,
+            pool,
+            poolTokenAccount,
+            recipientVault: recipient.vault,
+            recipientVaultTokenAccount: recipient.vaultTokenAccount,
+            feeVault,
+            feeVaultTokenAccount,
+            tokenProgram: TOKEN_PROGRAM_ID,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([authority])
+          .rpc();
+
+      await expectError(claimPool(Keypair.generate()), 'InvalidAuthority');
+      await claimPool(wallet.payer);
+      assert.strictEqual(await tokenBalance(recipient.vaultTokenAccount), targetAmount.toString());
+      assert.strictEqual(await connection.getAccountInfo(poolTokenAccount), null);
+
+      await program.methods
+        .closePoolContribution()
+        .accountsPartial({ payer: relayerKeypair.publicKey, pool, poolContribution })
+        .rpc();
+      await program.methods
+        .closePool()
+        .accountsPartial({
+          creator: wallet.publicKey,
+          pool,
+          poolTokenAccount: null,
+          creatorTokenAccount: null,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .rpc();
+      assert.strictEqual(await connection.getAccountInfo(pool), null);
+    });
+
+    it('sweeps stray tokens out of an expired pool before closing it', async () => {
+      const recipient = await createRecipient();
+      const { pool, poolTokenAccount } = await createPool(
+        recipient,
+        new BN(50_000),
+        Math.floor(Date.now() / 1000) + 3,
+      );
+      await provider.sendAndConfirm(
+        new Transaction().add(
+          createMintToInstruction(tokenMint, poolTokenAccount, wallet.publicKey, 1_000n),
+        ),
+      );
+      const creatorTokenAccount = await createTokenAccount(
+        provider,
+        wallet.publicKey,
+        tokenMint,
+        wallet.publicKey,
+      );
+      const closePool = (sweepTo: PublicKey | null) =>
+        program.methods
+          .closePool()
+          .accountsPartial({
+            creator: wallet.publicKey,
+            pool,
+            poolTokenAccount,
+            creatorTokenAccount: sweepTo,
+            tokenProgram: TOKEN_PROGRAM_ID,
+          })
+          .rpc();
+
+      await sleep(5_000);
+      await expectError(closePool(null), 'MissingCreatorTokenAccount');
+      await closePool(creatorTokenAccount);
+
+      assert.strictEqual(await tokenBalance(creatorTokenAccount), '1000');
+      assert.strictEqual(await connection.getAccountInfo(poolTokenAccount), null);
+      assert.strictEqual(await connection.getAccountInfo(pool), null);
+    });
+
+    it('settles a vault invoice once and closes expired ones', async () => {
+      const creator = await claimFundedVault(0n);
+      const payer = await claimTippingVault();
+      const stranger = await claimTippingVault();
+      const createInvoice = async (amount: BN, expiresAt: number) => {
+        const invoiceId = randomBytes(32);
+        const invoice = findPda(Buffer.from('invoice'), wallet.publicKey.toBuffer(), invoiceId);
+        await program.methods
+          .createInvoice({
+            invoiceId: Array.from(invoiceId) as number[],
+            payerHashedUserId: Array.from(payer.hashedUserId) as number[],
+            amount,
+            expiresAt: new BN(expiresAt),
+            reference: Array.from(Buffer.alloc(32)) as number[],
+          })
+          .accountsPartial({
+            authority: wallet.publicKey,
+            recipientVault: creator.vault,
+            invoice,
+            systemProgram: SystemProgram.programId,
+          })
+          .rpc();
+        return invoice;
+      };
+      const closeInvoice = (invoice: PublicKey) =>
+        program.methods
+          .closeInvoice()
+          .accountsPartial({ creator: wallet.publicKey, invoice, merchant: null })
+          .rpc();
+
+      const invoice = await createInvoice(new BN(40_000), 0);
+      await expectError(
+        payInvoice(stranger, invoice, creator.vaultTokenAccount, 1),
+        'InvalidInvoicePayer',
+      );
+      await payInvoice(payer, invoice, creator.vaultTokenAccount, 1);
+      assert.strictEqual(await tokenBalance(creator.vaultTokenAccount), '40000');
+      assert.strictEqual((await program.account.invoice.fetch(invoice)).paid, true);
+
+      await expectError(
+        payInvoice(payer, invoice, creator.vaultTokenAccount, 2),
+        'InvoiceAlreadyPaid',
+      );
+      await expectError(closeInvoice(invoice), 'InvoiceNotClosable');
+
+      const expiring = await createInvoice(new BN(10_000), Math.floor(Date.now() / 1000) + 2);
+      await sleep(4_000);
+      await expectError(
+        payInvoice(payer, expiring, creator.vaultTokenAccount, 3),
+        'InvoiceExpired',
+      );
+      await closeInvoice(expiring);
+      assert.strictEqual(await connection.getAccountInfo(expiring), null);
+    });
+
+    it('pays merchant invoices only from allowances without recipient rules', async () => {
+      const payer = await claimTippingVault();
+      const merchantAuthority = Keypair.generate();
+      await airdrop(merchantAuthority.publicKey);
+      const payoutTokenAccount = await createTokenAccount(
+        provider,
+        wallet.publicKey,
+        tokenMint,
+        merchantAuthority.publicKey,
+      );
+      const merchant = findPda(Buffer.from('merchant'), merchantAuthority.publicKey.toBuffer());
+
+      await program.methods
+        .registerMerchant()
+        .accountsPartial({
+          config: configPda,
+          upgradeAuthority: wallet.publicKey,
+          merchantAuthority: merchantAuthority.publicKey,
+          merchant,
+          payoutTokenAccount,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+
+      const invoiceId = randomBytes(32);
+      const invoice = findPda(
+        Buffer.from('invoice'),
+        merchantAuthority.publicKey.toBuffer(),
+        invoiceId,
+      );
+      await program.methods
+        .createMerchantInvoice({
+          invoiceId: Array.from(invoiceId) as number[],
+          payerHashedUserId: Array.from(payer.hashedUserId) as number[],
+          amount: new BN(30_000),
+          expiresAt: new BN(0),
+          reference: Array.from(Buffer.alloc(32, 7)) as number[],
+        })
+        .accountsPartial({
+          authority: merchantAuthority.publicKey,
+          merchant,
+          invoice,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([merchantAuthority])
+        .rpc();
+
+      const allowance = allowanceFor(payer);
+      const recipientRules = findPda(Buffer.from('recipient_rules'), allowance.toBuffer());
+      await program.methods
+        .addRecipientRule({ blocklist: {} }, Array.from(randomBytes(32)) as number[])
+        .accountsPartial({
+          authority: wallet.publicKey,
+          allowance,
+          recipientRules,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+      await expectError(
+        payInvoice(payer, invoice, payoutTokenAccount, 1, { merchant, recipientRules }),
+        'WalletRecipientRestricted',
+      );
+
+      await program.methods
+        .clearRecipientRules()
+        .accountsPartial({ authority: wallet.publicKey, allowance, recipientRules })
+        .rpc();
+      await payInvoice(payer, invoice, payoutTokenAccount, 1, { merchant });
+      assert.strictEqual(await tokenBalance(payoutTokenAccount), '30000');
+    });
+  });
 });